@@ -0,0 +1,92 @@
+//! 比较逐条调用与批量事务两种方式轮询 4 轴状态的延迟。
+//!
+//! 需要连接一块 USB1020：`cargo run --release --example batch_latency [次数]`
+
+use std::time::{Duration, Instant};
+use usb1020::common::Axis;
+use usb1020::register::{RR0, RR1, RR2};
+use usb1020::{Error, USB1020Controller};
+
+fn axes() -> [Axis; 4] {
+    [Axis::X, Axis::Y, Axis::Z, Axis::U]
+}
+
+fn poll_direct(controller: &USB1020Controller) -> Result<(), Error> {
+    controller.get_rr0()?;
+    for axis in axes() {
+        controller.get_rr1(axis)?;
+    }
+    for axis in axes() {
+        controller.get_rr2(axis)?;
+    }
+    Ok(())
+}
+
+/// [`poll_direct`] 发出的传输次数：每次读取 RR1、RR2 前都重新选择轴，
+/// 把同样的操作排入一个不执行的事务来计数
+fn direct_transfers(controller: &USB1020Controller) -> usize {
+    let mut transaction = controller.transaction();
+    transaction.read_rr(0);
+    for rr_no in [1, 2] {
+        for axis in axes() {
+            transaction.select(axis).read_rr(rr_no);
+        }
+    }
+    transaction.transfer_counts().0
+}
+
+/// 返回逐条执行与优化后的传输次数，以及实际发出的传输次数
+fn poll_batched(controller: &USB1020Controller) -> Result<(usize, usize, usize), Error> {
+    let mut transaction = controller.transaction();
+    let rr0 = transaction.read_rr(0);
+    let mut tickets = Vec::new();
+    for axis in axes() {
+        transaction.select(axis);
+        tickets.push((transaction.read_rr(1), transaction.read_rr(2)));
+    }
+
+    let (queued, optimized) = transaction.transfer_counts();
+    let responses = transaction.flush()?;
+    responses.decode::<RR0>(rr0)?;
+    for (rr1, rr2) in tickets {
+        responses.decode::<RR1>(rr1)?;
+        responses.decode::<RR2>(rr2)?;
+    }
+    Ok((queued, optimized, responses.transfers()))
+}
+
+fn measure(rounds: u32, mut f: impl FnMut() -> Result<(), Error>) -> Result<Duration, Error> {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        f()?;
+    }
+    Ok(start.elapsed() / rounds)
+}
+
+fn main() -> Result<(), Error> {
+    let rounds = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(100);
+    let controller = USB1020Controller::fast_open()?;
+
+    let direct = measure(rounds, || poll_direct(&controller))?;
+
+    let mut transfers = (0, 0, 0);
+    let batched = measure(rounds, || {
+        transfers = poll_batched(&controller)?;
+        Ok(())
+    })?;
+    let (queued, optimized, sent) = transfers;
+    println!(
+        "direct:  {:?} per poll, {} transfers",
+        direct,
+        direct_transfers(&controller)
+    );
+    println!(
+        "batched: {:?} per poll, {} transfers ({} queued, {} after optimize)",
+        batched, sent, queued, optimized
+    );
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::common::{Axes, Axis, OneOrAllAxis};
+use crate::register::*;
+use crate::{Error, USB1020Controller};
+use rusb::{GlobalContext, UsbContext};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+type Job<T> = Box<dyn FnOnce(&USB1020Controller<T>) + Send>;
+
+/// 控制器的 async 接口。
+///
+/// 阻塞的 USB 协议在独立的工作线程上执行，各方法只是把操作发给工作线程并等待结果，
+/// 不会阻塞异步运行时。同一时刻只有一个操作在执行，操作之间不会穿插。
+/// `AsyncController` 被 drop 时不等待工作线程，以免在异步运行时中阻塞；工作线程在
+/// 后台处理完剩余操作再退出，并按 [`DropPolicy`](crate::common::DropPolicy) 关闭控制器。
+pub struct AsyncController<T: UsbContext + 'static = GlobalContext> {
+    jobs: mpsc::Sender<Job<T>>,
+    poll_interval: Duration,
+}
+
+impl<T: UsbContext + 'static> AsyncController<T> {
+    pub fn new(controller: USB1020Controller<T>) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job<T>>();
+        // 不保留 JoinHandle：任务队列关闭后线程自行退出
+        thread::spawn(move || {
+            for job in receiver {
+                job(&controller);
+            }
+        });
+
+        Self {
+            jobs,
+            poll_interval: Duration::from_millis(10),
+        }
+    }
+
+    /// [`wait_drive_end`](Self::wait_drive_end) 轮询 RR0 的间隔，默认 10 ms
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// 在工作线程上执行一组操作，期间不会穿插其他操作
+    pub async fn run<R, F>(&self, f: F) -> Result<R, Error>
+    where
+        R: Send + 'static,
+        F: FnOnce(&USB1020Controller<T>) -> Result<R, Error> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |controller| {
+                let _ = sender.send(f(controller));
+            }))
+            .map_err(|_| Error::Disconnected)?;
+        receiver.await.map_err(|_| Error::Disconnected)?
+    }
+
+    pub async fn get_rr0(&self) -> Result<MainStatusRegister, Error> {
+        self.run(|controller| controller.get_rr0()).await
+    }
+
+    pub async fn get_rr1(&self, axis: Axis) -> Result<AxisStatusRegister, Error> {
+        self.run(move |controller| controller.get_rr1(axis)).await
+    }
+
+    pub async fn get_rr2(&self, axis: Axis) -> Result<AxisErrorRegister, Error> {
+        self.run(move |controller| controller.get_rr2(axis)).await
+    }
+
+    pub async fn get_rr3(&self) -> Result<RR3, Error> {
+        self.run(|controller| controller.get_rr3()).await
+    }
+
+    pub async fn get_rr4(&self) -> Result<RR4, Error> {
+        self.run(|controller| controller.get_rr4()).await
+    }
+
+    pub async fn read_lp(&self, axis: Axis) -> Result<u32, Error> {
+        self.run(move |controller| controller.read_lp(axis)).await
+    }
+
+    pub async fn read_ep(&self, axis: Axis) -> Result<u32, Error> {
+        self.run(move |controller| controller.read_ep(axis)).await
+    }
+
+    pub async fn read_cv(&self, axis: Axis) -> Result<u16, Error> {
+        self.run(move |controller| controller.read_cv(axis)).await
+    }
+
+    pub async fn read_ca(&self, axis: Axis) -> Result<u16, Error> {
+        self.run(move |controller| controller.read_ca(axis)).await
+    }
+
+    pub async fn set_lp(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.run(move |controller| controller.set_lp(axis, data))
+            .await
+    }
+
+    pub async fn set_ep(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.run(move |controller| controller.set_ep(axis, data))
+            .await
+    }
+
+    pub async fn decel_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.run(move |controller| controller.decel_stop(axis))
+            .await
+    }
+
+    pub async fn sudden_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.run(move |controller| controller.sudden_stop(axis))
+            .await
+    }
+
+    /// 轮询 RR0 直到指定的轴都结束驱动。
+    ///
+    /// 若某个轴因出错而停止，读取其 RR2 并返回对应的错误。
+    pub async fn wait_drive_end(&self, axes: Axes) -> Result<(), Error> {
+        loop {
+            let rr0 = self.get_rr0().await?;
+            if axes.iter().all(|axis| !rr0.drv(axis)) {
+                for axis in axes.iter().filter(|axis| rr0.error(*axis)) {
+                    self.get_rr2(axis).await?.check(axis)?;
+                }
+                return Ok(());
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
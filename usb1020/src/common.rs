@@ -1,6 +1,8 @@
 use deku::prelude::*;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Axis {
     X,
     Y,
@@ -9,6 +11,24 @@ pub enum Axis {
 }
 
 impl Axis {
+    /// 全部四个轴，按 X、Y、Z、U 的顺序
+    pub const ALL: [Axis; 4] = [Axis::X, Axis::Y, Axis::Z, Axis::U];
+
+    /// 轴在 [`Axis::ALL`] 中的序号
+    pub fn index(&self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+            Axis::U => 3,
+        }
+    }
+
+    /// 轴在命令高字节中对应的位
+    pub fn bit(&self) -> u8 {
+        1 << self.index()
+    }
+
     /// 形如 `0x10f` 的轴数，用于传输
     pub fn as_data(&self)->u16{
         match self {
@@ -20,13 +40,130 @@ impl Axis {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// 若干个轴的集合，按位与命令高字节中的轴号对应（X = 0x1，Y = 0x2，Z = 0x4，U = 0x8）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u8"))]
+pub struct Axes(u8);
+
+impl Axes {
+    pub const NONE: Axes = Axes(0);
+    pub const ALL: Axes = Axes(0x0f);
+
+    /// 由命令高字节中的轴号构造，忽略高 4 位
+    pub fn from_bits(bits: u8) -> Self {
+        Axes(bits & 0x0f)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(&self, axis: Axis) -> bool {
+        self.0 & axis.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// 按 X、Y、Z、U 的顺序遍历集合中的轴
+    pub fn iter(&self) -> impl Iterator<Item = Axis> {
+        let axes = *self;
+        Axis::ALL
+            .into_iter()
+            .filter(move |axis| axes.contains(*axis))
+    }
+}
+
+impl From<Axis> for Axes {
+    fn from(axis: Axis) -> Self {
+        Axes(axis.bit())
+    }
+}
+
+/// 严格的转换：含高 4 位时返回 [`Error::OutOfRange`](crate::Error::OutOfRange)，
+/// 而不像 [`Axes::from_bits`] 那样忽略
+impl TryFrom<u8> for Axes {
+    type Error = crate::Error;
+
+    fn try_from(bits: u8) -> Result<Self, Self::Error> {
+        if bits & !Axes::ALL.0 != 0 {
+            return Err(crate::Error::OutOfRange {
+                quantity: "axis mask",
+                value: bits as f64,
+            });
+        }
+        Ok(Axes::from_bits(bits))
+    }
+}
+
+impl From<OneOrAllAxis> for Axes {
+    fn from(axis: OneOrAllAxis) -> Self {
+        match axis {
+            OneOrAllAxis::One(axis) => axis.into(),
+            OneOrAllAxis::All => Axes::ALL,
+        }
+    }
+}
+
+impl<A: Into<Axes>> std::ops::BitOr<A> for Axes {
+    type Output = Axes;
+
+    fn bitor(self, rhs: A) -> Axes {
+        Axes(self.0 | rhs.into().0)
+    }
+}
+
+impl<A: Into<Axes>> std::ops::BitOr<A> for Axis {
+    type Output = Axes;
+
+    fn bitor(self, rhs: A) -> Axes {
+        Axes::from(self) | rhs
+    }
+}
+
+impl FromIterator<Axis> for Axes {
+    fn from_iter<I: IntoIterator<Item = Axis>>(iter: I) -> Self {
+        iter.into_iter().fold(Axes::NONE, |axes, axis| axes | axis)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum OneOrAllAxis {
     One(Axis),
     All,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl OneOrAllAxis {
+    /// 指定单个轴时返回该轴
+    pub fn one(&self) -> Option<Axis> {
+        match self {
+            OneOrAllAxis::One(axis) => Some(*axis),
+            OneOrAllAxis::All => None,
+        }
+    }
+}
+
+/// 控制器关闭（[`close`](crate::USB1020Controller::close) 或 drop）时对各轴采取的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DropPolicy {
+    /// 立即停止全部轴
+    SuddenStop,
+    /// 减速停止全部轴
+    #[default]
+    DecelStop,
+    /// 不停止，各轴保持当前的驱动
+    LeaveRunning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Clockwise {
     /// CW
     Clockwise,
@@ -34,13 +171,17 @@ pub enum Clockwise {
     CounterClockwise,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum DecelerationMode {
     Auto,
     Manual,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum LvDv {
     /// 定长驱动
     DV,
@@ -48,14 +189,19 @@ pub enum LvDv {
     LV,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum LineOrCurve {
     /// 直线
+    #[default]
     Line,
     /// S 曲线
     SCurve,
 }
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Direction {
     /// 反向
     Minus,
@@ -63,7 +209,9 @@ pub enum Direction {
     Plus,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[deku(
 type = "u8",
 bits = "1",
@@ -71,6 +219,7 @@ endian = "endian",
 ctx = "endian: deku::ctx::Endian")]
 pub enum CounterType {
     /// 逻辑位置计数器
+    #[default]
     #[deku(id = "0")]
     Logic,
     /// 实位计数器
@@ -78,7 +227,9 @@ pub enum CounterType {
     Fact,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[deku(
 type = "u8",
 bits = "1",
@@ -86,6 +237,7 @@ endian = "endian",
 ctx = "endian: deku::ctx::Endian")]
 pub enum InputMode {
     /// A/B相方式
+    #[default]
     #[deku(id = "0")]
     ABPhase,
     /// 上/下脉冲输入方式
@@ -0,0 +1,745 @@
+use crate::common::{Axis, CounterType, Direction, InputMode, LineOrCurve};
+use crate::profile::DEFAULT_ACCEL_OFFSET;
+use crate::register::{Alarm, InPos, PulseOutputMode};
+use crate::scale::AxisScale;
+use crate::transaction::Transaction;
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// 倍率 R 的取值范围，倍率 M = 8,000,000 / R
+pub const RANGE: std::ops::RangeInclusive<u32> = 16_000..=8_000_000;
+/// 初始速度 SV、驱动速度 V、加/减速度 A/D 与原点搜寻速度的取值范围。
+///
+/// V 取上限且 R 取下限时输出 4 Mpps，正是芯片的上限，因此两者各自在范围内即可。
+pub const SPEED: std::ops::RangeInclusive<u16> = 1..=8000;
+
+/// 整台设备的配置，由 [`USB1020Controller::apply_config`] 一次写入。
+///
+/// 启用 `config` 功能后可由 TOML 读入，见 [`MachineConfig::from_toml`]。各轴的配置位于
+/// `[axis.x]`、`[axis.y]` 等表中，未列出的轴与未写出的项保持默认值。
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct MachineConfig {
+    /// 各轴的配置，未列出的轴不写入
+    pub axis: BTreeMap<Axis, AxisConfig>,
+}
+
+/// 单个轴的配置
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct AxisConfig {
+    /// 轴的名称，仅用于显示
+    pub name: Option<String>,
+    /// 工程单位换算，见 [`USB1020Controller::scaled`]
+    pub scale: AxisScale,
+    /// 驱动脉冲的输出方式
+    pub pulse_mode: PulseOutputMode,
+    /// 驱动脉冲的有效电平
+    pub pulse_level: ActiveLevel,
+    /// 正向驱动时方向信号的电平（仅 1 脉冲方式）
+    pub direction_level: ActiveLevel,
+    /// 编码器输入
+    pub encoder: EncoderConfig,
+    /// 硬件与软件限位
+    pub limits: LimitConfig,
+    /// 速度曲线参数
+    pub drive: DriveConfig,
+    /// 伺服报警信号 ALARM
+    pub servo_alarm: Alarm,
+    /// 伺服到位信号 INPOS
+    pub in_position: InPos,
+    /// 自动原点搜寻参数
+    pub homing: Option<HomingConfig>,
+    /// 由面板按钮或手轮（EXPP/EXPM）驱动
+    pub external_drive: ExternalDriveConfig,
+    /// 输入信号的数字滤波
+    pub input_filter: InputFilterConfig,
+}
+
+impl Default for AxisConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            scale: AxisScale::default(),
+            pulse_mode: PulseOutputMode::default(),
+            pulse_level: ActiveLevel::High,
+            direction_level: ActiveLevel::High,
+            encoder: EncoderConfig::default(),
+            limits: LimitConfig::default(),
+            drive: DriveConfig::default(),
+            servo_alarm: Alarm::default(),
+            in_position: InPos::default(),
+            homing: None,
+            external_drive: ExternalDriveConfig::default(),
+            input_filter: InputFilterConfig::default(),
+        }
+    }
+}
+
+/// 信号的有效电平
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ActiveLevel {
+    /// 低电平有效
+    #[default]
+    Low,
+    /// 高电平有效
+    High,
+}
+
+/// 限位触发时的停止方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum StopMode {
+    /// 立即停止
+    #[default]
+    Sudden,
+    /// 减速停止
+    Decel,
+}
+
+/// 编码器输入 ECA/ECB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct EncoderConfig {
+    /// 输入方式
+    pub mode: InputMode,
+    /// A/B 相方式下的分频，取 1、2 或 4
+    pub division: u8,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self {
+            mode: InputMode::default(),
+            division: 1,
+        }
+    }
+}
+
+/// 限位配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct LimitConfig {
+    /// 限位触发时的停止方式，对硬件与软件限位都有效
+    pub stop: StopMode,
+    /// 正向限位信号 LMT+ 的有效电平
+    pub plus_level: ActiveLevel,
+    /// 反向限位信号 LMT- 的有效电平
+    pub minus_level: ActiveLevel,
+    /// 正向软件限位（COMP+），为 `None` 时不启用
+    pub software_plus: Option<i32>,
+    /// 反向软件限位（COMP-），为 `None` 时不启用
+    pub software_minus: Option<i32>,
+    /// 与软件限位比较的计数器
+    pub software_counter: CounterType,
+}
+
+/// 速度曲线参数，均为写入芯片的寄存器值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct DriveConfig {
+    /// 倍率 R
+    pub range: u32,
+    /// 初始速度 SV
+    pub start_speed: u16,
+    /// 驱动速度 V
+    pub speed: u16,
+    /// 加速度 A，未指定减速度时也用于减速
+    pub acceleration: u16,
+    /// 减速度 D，指定后加、减速分别设定（非对称加减速）
+    pub deceleration: Option<u16>,
+    /// 加速度变化率 K，S 曲线加减速时必须指定
+    pub jerk: Option<u16>,
+    /// 直线或 S 曲线加减速
+    pub curve: LineOrCurve,
+}
+
+impl Default for DriveConfig {
+    fn default() -> Self {
+        Self {
+            range: 8_000_000,
+            start_speed: 100,
+            speed: 100,
+            acceleration: 100,
+            deceleration: None,
+            jerk: None,
+            curve: LineOrCurve::default(),
+        }
+    }
+}
+
+impl DriveConfig {
+    /// 倍率 M
+    pub fn multiplier(&self) -> f64 {
+        8_000_000.0 / self.range as f64
+    }
+
+    /// 驱动速度对应的脉冲频率（pps）
+    pub fn speed_pps(&self) -> f64 {
+        self.speed as f64 * self.multiplier()
+    }
+
+    /// 检查各寄存器值，返回出错的项与原因
+    pub(crate) fn problems(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        if !RANGE.contains(&self.range) {
+            problems.push(("range", not_within(&RANGE, self.range)));
+        }
+        for (key, value) in [
+            ("start_speed", Some(self.start_speed)),
+            ("speed", Some(self.speed)),
+            ("acceleration", Some(self.acceleration)),
+            ("deceleration", self.deceleration),
+        ] {
+            match value {
+                Some(value) if !SPEED.contains(&value) => {
+                    problems.push((key, not_within(&SPEED, value)))
+                }
+                _ => {}
+            }
+        }
+        if self.start_speed > self.speed {
+            problems.push((
+                "start_speed",
+                format!(
+                    "must not exceed speed ({}), got {}",
+                    self.speed, self.start_speed
+                ),
+            ));
+        }
+        match self.jerk {
+            Some(0) => problems.push(("jerk", "must not be 0".to_owned())),
+            None if self.curve == LineOrCurve::SCurve => {
+                problems.push(("jerk", "is required for s_curve".to_owned()))
+            }
+            _ => {}
+        }
+        problems
+    }
+
+    /// 在 `wr3` 中设定本配置的加减速方式，并清除 D0 MANLD 回到自动减速。
+    ///
+    /// 由本配置决定的位为 D1 DSNDE（加、减速分别设定）与 D2 SACC（S 曲线）。手动减速的
+    /// 减速点 DP 只对写入时的曲线与脉冲数有效，换用新的曲线后不再保留。
+    pub(crate) fn wr3(&self, wr3: u16) -> u16 {
+        let mut wr3 = wr3 & !0b111;
+        if self.deceleration.is_some() {
+            wr3 |= 1 << 1;
+        }
+        if self.curve == LineOrCurve::SCurve {
+            wr3 |= 1 << 2;
+        }
+        wr3
+    }
+
+    /// 排入写入本配置所需的命令，`wr3` 为该轴当前的 WR3，返回写入后的 WR3，见 [`wr3`](Self::wr3)。
+    ///
+    /// `wr3` 选择了手动减速时同时把 AO 写回 [`DEFAULT_ACCEL_OFFSET`]。
+    pub(crate) fn queue<T: UsbContext>(
+        &self,
+        transaction: &mut Transaction<'_, T>,
+        axis: Axis,
+        wr3: u16,
+    ) -> u16 {
+        if wr3 & 1 != 0 {
+            transaction.write_data(axis, 0x0d, DEFAULT_ACCEL_OFFSET as i32 as u32);
+        }
+        let wr3 = self.wr3(wr3);
+
+        transaction
+            .select(axis)
+            .write_pipe(3, wr3)
+            .write_data(axis, 0x00, self.range)
+            .write_data(axis, 0x02, self.acceleration.into())
+            .write_data(axis, 0x04, self.start_speed.into())
+            .write_data(axis, 0x05, self.speed.into());
+        if let Some(jerk) = self.jerk {
+            transaction.write_data(axis, 0x01, jerk.into());
+        }
+        if let Some(deceleration) = self.deceleration {
+            transaction.write_data(axis, 0x03, deceleration.into());
+        }
+        wr3
+    }
+}
+
+fn not_within<V: fmt::Display>(range: &std::ops::RangeInclusive<V>, value: V) -> String {
+    format!(
+        "must be within {}..={}, got {}",
+        range.start(),
+        range.end(),
+        value
+    )
+}
+
+/// 外部信号 EXPP/EXPM 的驱动方式（WR3 D3～D4）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ExternalDriveMode {
+    /// 不使用外部信号驱动
+    #[default]
+    Disabled,
+    /// 信号有效期间向对应方向连续驱动
+    Continuous,
+    /// 信号每次变为有效时向对应方向定长驱动
+    FixedPulse,
+    /// 手轮：EXPP/EXPM 接 A/B 相，每个脉冲按相位方向定长驱动
+    ManualPulser,
+}
+
+/// 由面板按钮或手轮直接驱动，不经过主机
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct ExternalDriveConfig {
+    pub mode: ExternalDriveMode,
+    /// 定长驱动与手轮方式下每次驱动的脉冲数，写入 P，之后的定长驱动会改写它
+    pub pulses: u32,
+}
+
+impl Default for ExternalDriveConfig {
+    fn default() -> Self {
+        Self {
+            mode: ExternalDriveMode::Disabled,
+            pulses: 1,
+        }
+    }
+}
+
+impl ExternalDriveConfig {
+    /// 在 `wr3` 中设定本配置的 EXOP 位
+    pub(crate) fn wr3(&self, wr3: u16) -> u16 {
+        let exop = match self.mode {
+            ExternalDriveMode::Disabled => 0b00,
+            ExternalDriveMode::Continuous => 0b01,
+            ExternalDriveMode::FixedPulse => 0b10,
+            ExternalDriveMode::ManualPulser => 0b11,
+        };
+        wr3 & !(0b11 << 3) | exop << 3
+    }
+
+    /// 检查各项，返回出错的项与原因
+    pub(crate) fn problems(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        if self.uses_pulses() && self.pulses == 0 {
+            problems.push(("pulses", "must not be 0".to_owned()));
+        }
+        problems
+    }
+
+    /// 是否需要写入每次驱动的脉冲数
+    pub(crate) fn uses_pulses(&self) -> bool {
+        matches!(
+            self.mode,
+            ExternalDriveMode::FixedPulse | ExternalDriveMode::ManualPulser
+        )
+    }
+}
+
+/// 输入滤波的时间常数（WR3 D13～D15），脉宽小于该值的噪声被滤除
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FilterTime {
+    /// 1.75 µs
+    #[default]
+    Ns1750 = 0,
+    /// 224 µs
+    Us224 = 1,
+    /// 448 µs
+    Us448 = 2,
+    /// 896 µs
+    Us896 = 3,
+    /// 1.792 ms
+    Us1792 = 4,
+    /// 3.584 ms
+    Us3584 = 5,
+    /// 7.168 ms
+    Us7168 = 6,
+    /// 14.336 ms
+    Us14336 = 7,
+}
+
+impl FilterTime {
+    /// 时间常数，脉宽小于该值的输入被滤除
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(match self {
+            FilterTime::Ns1750 => 1_750,
+            FilterTime::Us224 => 224_000,
+            FilterTime::Us448 => 448_000,
+            FilterTime::Us896 => 896_000,
+            FilterTime::Us1792 => 1_792_000,
+            FilterTime::Us3584 => 3_584_000,
+            FilterTime::Us7168 => 7_168_000,
+            FilterTime::Us14336 => 14_336_000,
+        })
+    }
+}
+
+/// 输入信号的数字滤波（WR3 D8～D15），各组分别启用，时间常数为该轴各组共用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct InputFilterConfig {
+    /// EMG、LMT+/LMT-、IN0、IN1（FE0）
+    pub limits: bool,
+    /// IN2（FE1）
+    pub in2: bool,
+    /// INPOS、ALARM（FE2）
+    pub servo: bool,
+    /// EXPP、EXPM（FE3）
+    pub external: bool,
+    /// IN3（FE4）
+    pub in3: bool,
+    /// 时间常数（FL）
+    pub time: FilterTime,
+}
+
+/// 与初始化写入的 WR3 一致：除 IN3 外均启用滤波，时间常数 1.75 µs
+impl Default for InputFilterConfig {
+    fn default() -> Self {
+        Self {
+            limits: true,
+            in2: true,
+            servo: true,
+            external: true,
+            in3: false,
+            time: FilterTime::default(),
+        }
+    }
+}
+
+impl InputFilterConfig {
+    /// 在 `wr3` 中设定本配置的 FE0～FE4 与 FL 位
+    pub(crate) fn wr3(&self, wr3: u16) -> u16 {
+        let enables = [self.limits, self.in2, self.servo, self.external, self.in3];
+        let mut bits = (self.time as u16) << 13;
+        for (bit, enabled) in enables.into_iter().enumerate() {
+            if enabled {
+                bits |= 1 << (8 + bit);
+            }
+        }
+        wr3 & 0x00ff | bits
+    }
+}
+
+/// 自动原点搜寻方式设定（命令码 0x60）
+const HOMING_MODE: u8 = 0x60;
+/// 原点搜寻速度 HV（命令码 0x61）
+const HOMING_SPEED: u8 = 0x61;
+
+/// 自动原点搜寻参数。
+///
+/// 第 1 步以驱动速度朝 `direction` 搜寻近原点信号 IN0，第 2 步以原点搜寻速度 HV 搜寻
+/// 原点信号 IN1；`offset` 不为 0 时第 4 步以驱动速度移动偏移量。不使用第 3 步（编码器 Z 相）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct HomingConfig {
+    /// 搜寻方向
+    pub direction: Direction,
+    /// 原点搜寻速度 HV，与驱动速度使用同一倍率
+    pub speed: u16,
+    /// 找到原点后再移动的脉冲数，写入 P，之后的定长驱动会改写它
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset: i32,
+}
+
+impl HomingConfig {
+    /// 检查各项，返回出错的项与原因
+    pub(crate) fn problems(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        if !SPEED.contains(&self.speed) {
+            problems.push(("speed", not_within(&SPEED, self.speed)));
+        }
+        problems
+    }
+
+    /// 原点搜寻方式：各步的允许位 STnE 在 D0/D2/D4/D6，方向位 STnD 在 D1/D3/D5/D7（1 为反向）
+    pub(crate) fn mode(&self) -> u16 {
+        let step = |index: u16, direction: Direction| {
+            let minus = match direction {
+                Direction::Plus => 0,
+                Direction::Minus => 1,
+            };
+            (0b01 | minus << 1) << (2 * index)
+        };
+        let mut mode = step(0, self.direction) | step(1, self.direction);
+        if self.offset != 0 {
+            let direction = if self.offset > 0 {
+                Direction::Plus
+            } else {
+                Direction::Minus
+            };
+            mode |= step(3, direction);
+        }
+        mode
+    }
+}
+
+impl AxisConfig {
+    /// 由本配置得出的 WR2
+    pub(crate) fn wr2(&self) -> u16 {
+        let limits = &self.limits;
+        let mut wr2 = 0;
+        let mut set = |bit: u16, value: bool| {
+            if value {
+                wr2 |= 1 << bit;
+            }
+        };
+        set(0, limits.software_plus.is_some());
+        set(1, limits.software_minus.is_some());
+        set(2, limits.stop == StopMode::Decel);
+        set(3, limits.plus_level == ActiveLevel::High);
+        set(4, limits.minus_level == ActiveLevel::High);
+        set(5, limits.software_counter == CounterType::Fact);
+        set(6, self.pulse_mode == PulseOutputMode::PulseDirection);
+        set(7, self.pulse_level == ActiveLevel::Low);
+        set(8, self.direction_level == ActiveLevel::High);
+        set(9, self.encoder.mode == InputMode::UpDownPulse);
+        set(10, self.encoder.division == 2);
+        set(11, self.encoder.division == 4);
+        set(12, self.servo_alarm == Alarm::EnableWithLog);
+        set(13, self.servo_alarm != Alarm::Disable);
+        set(14, self.in_position == InPos::EnableWithLog);
+        set(15, self.in_position != InPos::Disable);
+        wr2
+    }
+}
+
+/// 配置中的一处错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// 出错的项，形如 `axis.x.drive.speed`
+    pub key: String,
+    /// 该项在 TOML 文件中的行号（从 1 开始），配置不是由文件读入时为 `None`
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// 将检查出的问题转为 [`Error::InvalidConfig`]，各项的键加上前缀 `prefix`
+pub(crate) fn check_problems(
+    prefix: &str,
+    problems: Vec<(&'static str, String)>,
+) -> Result<(), Error> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(Error::InvalidConfig(
+        problems
+            .into_iter()
+            .map(|(key, message)| ConfigError {
+                key: format!("{}{}", prefix, key),
+                line: None,
+                message,
+            })
+            .collect(),
+    ))
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+pub(crate) fn describe(errors: &[ConfigError]) -> String {
+    let errors: Vec<String> = errors.iter().map(ConfigError::to_string).collect();
+    errors.join("; ")
+}
+
+pub(crate) fn axis_key(axis: Axis) -> &'static str {
+    match axis {
+        Axis::X => "x",
+        Axis::Y => "y",
+        Axis::Z => "z",
+        Axis::U => "u",
+    }
+}
+
+impl MachineConfig {
+    /// 按芯片的取值范围检查配置，返回找到的全部错误
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        for (&axis, config) in &self.axis {
+            let mut error = |key: &str, message: String| {
+                errors.push(ConfigError {
+                    key: format!("axis.{}.{}", axis_key(axis), key),
+                    line: None,
+                    message,
+                })
+            };
+
+            for (key, message) in config.scale.problems() {
+                error(&format!("scale.{}", key), message);
+            }
+            if ![1, 2, 4].contains(&config.encoder.division) {
+                error(
+                    "encoder.division",
+                    format!("must be 1, 2 or 4, got {}", config.encoder.division),
+                );
+            }
+
+            let limits = &config.limits;
+            if let (Some(plus), Some(minus)) = (limits.software_plus, limits.software_minus) {
+                if plus <= minus {
+                    error(
+                        "limits.software_plus",
+                        format!(
+                            "must be greater than software_minus ({}), got {}",
+                            minus, plus
+                        ),
+                    );
+                }
+            }
+
+            for (key, message) in config.drive.problems() {
+                error(&format!("drive.{}", key), message);
+            }
+            for (key, message) in config.external_drive.problems() {
+                error(&format!("external_drive.{}", key), message);
+            }
+            if let Some(homing) = &config.homing {
+                for (key, message) in homing.problems() {
+                    error(&format!("homing.{}", key), message);
+                }
+                if homing.offset != 0 && config.external_drive.uses_pulses() {
+                    error(
+                        "homing.offset",
+                        "shares the output pulse count P with external_drive.pulses".to_owned(),
+                    );
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl MachineConfig {
+    /// 由 TOML 读入配置并检查，错误信息中带有行号
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        let document = toml_edit::ImDocument::parse(text.to_owned())
+            .map_err(|e| Error::ConfigSyntax(e.to_string()))?;
+        let config: Self = toml_edit::de::from_document(document.clone())
+            .map_err(|e| Error::ConfigSyntax(e.to_string()))?;
+        config.validate().map_err(|mut errors| {
+            for error in &mut errors {
+                error.line = locate(&document, &error.key);
+            }
+            Error::InvalidConfig(errors)
+        })?;
+        Ok(config)
+    }
+
+    /// 输出为 TOML
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml_edit::ser::to_string_pretty(self).map_err(|e| Error::ConfigSyntax(e.to_string()))
+    }
+}
+
+/// 找出 `key` 在文件中的行号，该项未写出（取默认值）时退而取其所在的表
+#[cfg(feature = "config")]
+fn locate(document: &toml_edit::ImDocument<String>, key: &str) -> Option<usize> {
+    let mut item = document.as_item();
+    let mut span = None;
+    for part in key.split('.') {
+        match item.get(part) {
+            Some(next) => {
+                item = next;
+                span = next.span().or(span);
+            }
+            None => break,
+        }
+    }
+    let start = span?.start;
+    Some(document.raw()[..start].matches('\n').count() + 1)
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 检查并写入配置。
+    ///
+    /// 对配置中的每个轴写入 WR2、WR3 中与配置有关的位、速度曲线参数、软件限位、外部驱动方式、
+    /// 输入滤波与原点搜寻参数，全部写入在一个事务中完成。配置不合法时不写入任何内容。
+    pub fn apply_config(&self, config: &MachineConfig) -> Result<(), Error> {
+        let apply = || {
+            config.validate().map_err(Error::InvalidConfig)?;
+
+            let mut modes = self.modes.get();
+            let mut ranges = self.ranges.get();
+            let mut transaction = self.transaction();
+            for (&axis, axis_config) in &config.axis {
+                let mode = &mut modes[axis.index()];
+                mode.wr2 = axis_config.wr2();
+                transaction.select(axis).write_pipe(2, mode.wr2);
+                let external = &axis_config.external_drive;
+                let wr3 = axis_config.input_filter.wr3(external.wr3(mode.wr3));
+                mode.wr3 = axis_config.drive.queue(&mut transaction, axis, wr3);
+                ranges[axis.index()] = axis_config.drive.range;
+                if external.uses_pulses() {
+                    transaction.write_data(axis, 0x06, external.pulses);
+                }
+
+                let limits = &axis_config.limits;
+                if let Some(plus) = limits.software_plus {
+                    transaction.write_data(axis, 0x0b, plus as u32);
+                }
+                if let Some(minus) = limits.software_minus {
+                    transaction.write_data(axis, 0x0c, minus as u32);
+                }
+
+                if let Some(homing) = &axis_config.homing {
+                    transaction
+                        .write_data(axis, HOMING_SPEED, homing.speed.into())
+                        .write_data(axis, HOMING_MODE, homing.mode().into());
+                    if homing.offset != 0 {
+                        transaction.write_data(axis, 0x06, homing.offset.unsigned_abs());
+                    }
+                }
+            }
+            transaction.flush()?;
+            self.modes.set(modes);
+            self.ranges.set(ranges);
+            Ok(())
+        };
+        apply().during("apply_config", None)
+    }
+
+    /// 设定 `axis` 的输入滤波。
+    ///
+    /// 接触不良或受干扰的限位开关会使驱动误停，启用 `limits` 并加大时间常数即可滤除；
+    /// 时间常数同时使信号的响应延迟相应的时间。
+    pub fn set_input_filter(&self, axis: Axis, filter: &InputFilterConfig) -> Result<(), Error> {
+        let apply = || {
+            let mut modes = self.modes.get();
+            let wr3 = filter.wr3(modes[axis.index()].wr3);
+            let mut transaction = self.transaction();
+            transaction.select(axis).write_pipe(3, wr3);
+            transaction.flush()?;
+            modes[axis.index()].wr3 = wr3;
+            self.modes.set(modes);
+            Ok(())
+        };
+        apply().during("set_input_filter", Some(axis))
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{Error, USB1020Controller};
+use rusb::{Device, GlobalContext, UsbContext};
+use std::fmt;
+use std::time::Duration;
+
+/// 打开设备时的选项，见 [`USB1020Controller::from_handle_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// 接口 0 上有内核驱动时将其卸载，并在关闭时重新挂载（仅 Linux 支持）
+    pub detach_kernel_driver: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            detach_kernel_driver: true,
+        }
+    }
+}
+
+/// 一块已连接的 USB1020 在总线上的位置与描述信息
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    /// 总线号
+    pub bus: u8,
+    /// 从根集线器到设备经过的端口号
+    pub port_path: Vec<u8>,
+    /// 总线上的地址，设备重新插拔后会改变
+    pub address: u8,
+    /// 序列号字符串，设备未提供或无权限读取时为 `None`
+    pub serial: Option<String>,
+}
+
+impl DeviceInfo {
+    const STRING_TIMEOUT: Duration = Duration::from_millis(100);
+
+    pub(crate) fn from_device<T: UsbContext>(device: &Device<T>) -> Result<Self, Error> {
+        let descriptor = device.device_descriptor()?;
+        let serial = descriptor.serial_number_string_index().and_then(|_| {
+            let handle = device.open().ok()?;
+            let language = *handle.read_languages(Self::STRING_TIMEOUT).ok()?.first()?;
+            handle
+                .read_serial_number_string(language, &descriptor, Self::STRING_TIMEOUT)
+                .ok()
+        });
+
+        Ok(Self {
+            bus: device.bus_number(),
+            port_path: device.port_numbers()?,
+            address: device.address(),
+            serial,
+        })
+    }
+
+    /// 形如 `1-3.2` 的端口路径（与 Linux sysfs 中的设备名一致），设备插在同一个口上时保持不变
+    pub fn path(&self) -> String {
+        format_path(self.bus, &self.port_path)
+    }
+
+    /// 解析形如 `1-3.2` 的端口路径，返回总线号与端口号
+    pub fn parse_path(path: &str) -> Option<(u8, Vec<u8>)> {
+        let (bus, ports) = path.split_once('-')?;
+        let bus = bus.parse().ok()?;
+        let ports = ports
+            .split('.')
+            .map(|port| port.parse().ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some((bus, ports))
+    }
+
+    pub(crate) fn matches<T: UsbContext>(&self, device: &Device<T>) -> bool {
+        device.bus_number() == self.bus
+            && device.address() == self.address
+            && matches!(device.port_numbers(), Ok(ports) if ports == self.port_path)
+    }
+}
+
+fn format_path(bus: u8, port_path: &[u8]) -> String {
+    let ports: Vec<String> = port_path.iter().map(u8::to_string).collect();
+    format!("{}-{}", bus, ports.join("."))
+}
+
+/// 设备的端口路径，不需要打开设备，可以在热插拔回调中使用
+pub(crate) fn device_path<T: UsbContext>(device: &Device<T>) -> Option<String> {
+    let port_path = device.port_numbers().ok()?;
+    Some(format_path(device.bus_number(), &port_path))
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "USB1020 at {} (address {})", self.path(), self.address)?;
+        if let Some(serial) = &self.serial {
+            write!(f, ", serial {}", serial)?;
+        }
+        Ok(())
+    }
+}
+
+/// 列出所有已连接的 USB1020
+pub fn list_devices() -> Result<Vec<DeviceInfo>, Error> {
+    list_devices_with(&GlobalContext::default())
+}
+
+/// 在给定的 libusb 上下文中列出所有已连接的 USB1020
+pub fn list_devices_with<T: UsbContext>(context: &T) -> Result<Vec<DeviceInfo>, Error> {
+    context
+        .devices()?
+        .iter()
+        .filter(USB1020Controller::device_filter)
+        .map(|device| DeviceInfo::from_device(&device))
+        .collect()
+}
@@ -0,0 +1,339 @@
+use crate::common::{Axes, Axis, DecelerationMode, Direction, OneOrAllAxis};
+use crate::config::{check_problems, DriveConfig, ExternalDriveConfig};
+use crate::profile::{ManualDeceleration, DEFAULT_ACCEL_OFFSET};
+use crate::register::{AxisStatusRegister, MainStatusRegister};
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+
+/// 倍率 R 为 8,000,000 时倍率 M 为 1，初始化时写入该值
+pub(crate) const DEFAULT_RANGE: u32 = 8_000_000;
+
+/// 命令字：高字节为轴号，低字节为命令码
+pub(crate) fn command_word(axes: impl Into<Axes>, code: u8) -> u16 {
+    u16::from(axes.into().bits()) << 8 | u16::from(code)
+}
+
+/// 驱动中的一项改写是否生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// 未要求改写
+    Unchanged,
+    /// 已写入
+    Applied,
+    /// 当前阶段芯片会忽略改写，在连续驱动中改写输出脉冲数，或者在 S 曲线驱动中改写驱动速度，未写入
+    Rejected,
+    /// 该轴未在驱动，未写入
+    Idle,
+}
+
+/// [`USB1020Controller::change_drive`] 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriveChange {
+    /// 驱动速度 V 的改写
+    pub speed: Change,
+    /// 输出脉冲数 P 的改写
+    pub pulses: Change,
+    /// 判断时读到的 RR1
+    pub status: AxisStatusRegister,
+}
+
+impl DriveChange {
+    /// 按驱动状态决定各项改写是否写入，`continuous` 为该轴正在连续驱动，
+    /// `s_curve` 为该轴的 WR3 选择了 S 曲线加减速
+    pub(crate) fn decide(
+        status: AxisStatusRegister,
+        driving: bool,
+        continuous: bool,
+        s_curve: bool,
+        speed: bool,
+        pulses: bool,
+    ) -> Self {
+        let decide = |requested: bool, accepted: bool| match (requested, driving, accepted) {
+            (false, _, _) => Change::Unchanged,
+            (true, false, _) => Change::Idle,
+            (true, true, true) => Change::Applied,
+            (true, true, false) => Change::Rejected,
+        };
+        Self {
+            speed: decide(speed, !s_curve && status.accepts_speed_change()),
+            pulses: decide(pulses, !continuous && status.accepts_pulse_change()),
+            status,
+        }
+    }
+}
+
+/// 速度曲线参数与驱动命令。
+///
+/// 这里的参数都是写入芯片的寄存器值，实际速度与倍率有关：速度（pps）= V × M，
+/// 加速度（pps/s）= A × 125 × M，倍率 M = 8,000,000 / R。
+impl<T: UsbContext> USB1020Controller<T> {
+    fn write_data(&self, axis: OneOrAllAxis, code: u8, data: u32) -> Result<(), Error> {
+        self.set_u32_data(data)
+            .and_then(|_| self.write_pipe(0, command_word(axis, code)))
+    }
+
+    /// 最近一次写入的倍率 R
+    pub fn range(&self, axis: Axis) -> u32 {
+        self.ranges.get()[axis.index()]
+    }
+
+    /// 倍率 M = 8,000,000 / R
+    pub fn multiplier(&self, axis: Axis) -> f64 {
+        8_000_000.0 / self.range(axis) as f64
+    }
+
+    /// 写入一组速度曲线参数，并按其设定 WR3 中的加减速方式。
+    ///
+    /// 参数全部在一个事务中写入，不合法时不写入任何内容。该轴回到自动减速，需要手动减速时
+    /// 在本方法之后调用 [`set_manual_deceleration`](Self::set_manual_deceleration)。
+    pub fn set_drive(&self, axis: Axis, drive: &DriveConfig) -> Result<(), Error> {
+        let apply = || {
+            check_problems("", drive.problems())?;
+
+            let mut modes = self.modes.get();
+            let mut transaction = self.transaction();
+            let wr3 = drive.queue(&mut transaction, axis, modes[axis.index()].wr3);
+            transaction.flush()?;
+
+            modes[axis.index()].wr3 = wr3;
+            self.modes.set(modes);
+            let mut ranges = self.ranges.get();
+            ranges[axis.index()] = drive.range;
+            self.ranges.set(ranges);
+            Ok(())
+        };
+        apply().during("set_drive", Some(axis))
+    }
+
+    /// 写入倍率 R（16,000～8,000,000）
+    pub fn set_range(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.write_data(axis, 0x00, data)
+            .during("set_range", axis.one())?;
+        let mut ranges = self.ranges.get();
+        for axis in Axes::from(axis).iter() {
+            ranges[axis.index()] = data;
+        }
+        self.ranges.set(ranges);
+        Ok(())
+    }
+
+    /// 写入加速度变化率 K（1～65,535）
+    pub fn set_jerk(&self, axis: OneOrAllAxis, data: u16) -> Result<(), Error> {
+        self.write_data(axis, 0x01, data.into())
+            .during("set_jerk", axis.one())
+    }
+
+    /// 写入加速度 A（1～8,000）
+    pub fn set_acceleration(&self, axis: OneOrAllAxis, data: u16) -> Result<(), Error> {
+        self.write_data(axis, 0x02, data.into())
+            .during("set_acceleration", axis.one())
+    }
+
+    /// 写入减速度 D（1～8,000），仅在加、减速分别设定时有效
+    pub fn set_deceleration(&self, axis: OneOrAllAxis, data: u16) -> Result<(), Error> {
+        self.write_data(axis, 0x03, data.into())
+            .during("set_deceleration", axis.one())
+    }
+
+    /// 写入初始速度 SV（1～8,000）
+    pub fn set_start_speed(&self, axis: OneOrAllAxis, data: u16) -> Result<(), Error> {
+        self.write_data(axis, 0x04, data.into())
+            .during("set_start_speed", axis.one())
+    }
+
+    /// 写入驱动速度 V（1～8,000）
+    pub fn set_speed(&self, axis: OneOrAllAxis, data: u16) -> Result<(), Error> {
+        self.write_data(axis, 0x05, data.into())
+            .during("set_speed", axis.one())
+    }
+
+    /// 写入定长驱动的输出脉冲数 P
+    pub fn set_pulse(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.write_data(axis, 0x06, data)
+            .during("set_pulse", axis.one())
+    }
+
+    /// 写入手动减速点 DP
+    pub fn set_decel_point(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.write_data(axis, 0x07, data)
+            .during("set_decel_point", axis.one())
+    }
+
+    /// 写入加速计数器偏移 AO（复位后为 8）
+    pub fn set_accel_offset(&self, axis: OneOrAllAxis, data: i16) -> Result<(), Error> {
+        self.write_data(axis, 0x0d, data as i32 as u32)
+            .during("set_accel_offset", axis.one())
+    }
+
+    /// 设定定长驱动的减速点由芯片自动计算还是使用 DP（WR3 D0 MANLD）。
+    ///
+    /// 切换到自动减速时同时把 AO 写回 [`DEFAULT_ACCEL_OFFSET`]，不保留手动减速时的提前量。
+    pub fn set_deceleration_mode(&self, axis: Axis, mode: DecelerationMode) -> Result<(), Error> {
+        let mut modes = self.modes.get();
+        let mut transaction = self.transaction();
+        let wr3 = match mode {
+            DecelerationMode::Auto => {
+                transaction.write_data(axis, 0x0d, DEFAULT_ACCEL_OFFSET as i32 as u32);
+                modes[axis.index()].wr3 & !1
+            }
+            DecelerationMode::Manual => modes[axis.index()].wr3 | 1,
+        };
+        transaction.select(axis).write_pipe(3, wr3);
+        transaction
+            .flush()
+            .during("set_deceleration_mode", Some(axis))?;
+        modes[axis.index()].wr3 = wr3;
+        self.modes.set(modes);
+        Ok(())
+    }
+
+    /// 设定由面板按钮或手轮（EXPP/EXPM）驱动的方式，定长与手轮方式下同时写入 P。
+    ///
+    /// 外部驱动使用该轴已写入的速度曲线；手轮方式下驱动速度须足以跟上手轮脉冲。
+    ///
+    /// 外部驱动与主机发出的定长驱动共用 P：[`prepare_moves`](Self::prepare_moves)、
+    /// [`probe`](Self::probe)、[`set_manual_deceleration`](Self::set_manual_deceleration) 等
+    /// 写入 P 之后，面板的每次驱动也按新的脉冲数输出，不会自动恢复。主机驱动结束后须
+    /// 再调用本方法写回外部驱动的脉冲数。
+    pub fn set_external_drive(
+        &self,
+        axis: Axis,
+        external: &ExternalDriveConfig,
+    ) -> Result<(), Error> {
+        let apply = || {
+            check_problems("", external.problems())?;
+            let mut modes = self.modes.get();
+            let wr3 = external.wr3(modes[axis.index()].wr3);
+            let mut transaction = self.transaction();
+            transaction.select(axis).write_pipe(3, wr3);
+            if external.uses_pulses() {
+                transaction.write_data(axis, 0x06, external.pulses);
+            }
+            transaction.flush()?;
+            modes[axis.index()].wr3 = wr3;
+            self.modes.set(modes);
+            Ok(())
+        };
+        apply().during("set_external_drive", Some(axis))
+    }
+
+    /// 为 `drive` 曲线下输出 `pulses` 个脉冲的定长驱动计算并写入 P、DP 与 AO，
+    /// 并切换到手动减速。
+    ///
+    /// `drive` 应与已写入该轴的速度曲线参数一致，返回写入的减速点。之后再写入速度曲线
+    /// （[`set_drive`](Self::set_drive)、[`prepare_moves`](Self::prepare_moves)、
+    /// [`probe`](Self::probe) 等）会回到自动减速。
+    pub fn set_manual_deceleration(
+        &self,
+        axis: Axis,
+        drive: &DriveConfig,
+        pulses: u32,
+    ) -> Result<ManualDeceleration, Error> {
+        let manual =
+            ManualDeceleration::new(drive, pulses).during("set_manual_deceleration", Some(axis))?;
+        let mut modes = self.modes.get();
+        let wr3 = modes[axis.index()].wr3 | 1;
+        let mut transaction = self.transaction();
+        transaction
+            .write_data(axis, 0x06, pulses)
+            .write_data(axis, 0x07, manual.decel_point)
+            .write_data(axis, 0x0d, manual.offset as i32 as u32)
+            .select(axis)
+            .write_pipe(3, wr3);
+        transaction
+            .flush()
+            .during("set_manual_deceleration", Some(axis))?;
+        modes[axis.index()].wr3 = wr3;
+        self.modes.set(modes);
+        Ok(manual)
+    }
+
+    /// 在驱动中改写驱动速度 V 与（定长驱动的）输出脉冲数 P。
+    ///
+    /// 先读取 RR0 与 RR1，只写入当前阶段能够生效的改写，见
+    /// [`accepts_speed_change`](AxisStatusRegister::accepts_speed_change) 与
+    /// [`accepts_pulse_change`](AxisStatusRegister::accepts_pulse_change)。读取与写入之间
+    /// 驱动可能进入下一阶段，此时改写仍可能被忽略。新的输出脉冲数小于已输出的脉冲数时，
+    /// 驱动立即停止。
+    ///
+    /// 该轴未在驱动时不写入，结果为 [`Change::Idle`]，需要改写下一次驱动的参数时请用
+    /// [`set_speed`](Self::set_speed) 等方法。最近一次由本库发出的是连续驱动时，
+    /// 改写输出脉冲数为 [`Change::Rejected`]；由同步动作或外部信号启动的驱动无法区分。
+    /// S 曲线加减速的驱动在整个过程中都会忽略驱动速度的改写，该轴的 WR3 选择了 S 曲线时
+    /// 改写驱动速度总是 [`Change::Rejected`]。
+    pub fn change_drive(
+        &self,
+        axis: Axis,
+        speed: Option<u16>,
+        pulses: Option<u32>,
+    ) -> Result<DriveChange, Error> {
+        let apply = || {
+            let mut transaction = self.transaction();
+            let rr0 = transaction.read_rr(0);
+            let rr1 = transaction.select(axis).read_rr(1);
+            let responses = transaction.flush()?;
+            let main: MainStatusRegister = responses.decode(rr0)?;
+            let change = DriveChange::decide(
+                responses.decode(rr1)?,
+                main.drv(axis),
+                self.continuous.get().contains(axis),
+                self.modes.get()[axis.index()].wr3 & 0b100 != 0,
+                speed.is_some(),
+                pulses.is_some(),
+            );
+
+            let mut transaction = self.transaction();
+            if let (Some(speed), Change::Applied) = (speed, change.speed) {
+                transaction.write_data(axis, 0x05, speed.into());
+            }
+            if let (Some(pulses), Change::Applied) = (pulses, change.pulses) {
+                transaction.write_data(axis, 0x06, pulses);
+            }
+            transaction.flush()?;
+            Ok(change)
+        };
+        apply().during("change_drive", Some(axis))
+    }
+
+    /// 在驱动中改写驱动速度 V，见 [`change_drive`](Self::change_drive)
+    pub fn change_speed(&self, axis: Axis, speed: u16) -> Result<Change, Error> {
+        Ok(self.change_drive(axis, Some(speed), None)?.speed)
+    }
+
+    /// 在定长驱动中改写输出脉冲数 P，见 [`change_drive`](Self::change_drive)
+    pub fn change_pulse(&self, axis: Axis, pulses: u32) -> Result<Change, Error> {
+        Ok(self.change_drive(axis, None, Some(pulses))?.pulses)
+    }
+
+    /// 按已写入的输出脉冲数向指定方向定长驱动
+    pub fn fixed_drive(&self, axis: OneOrAllAxis, direction: Direction) -> Result<(), Error> {
+        let code = match direction {
+            Direction::Plus => 0x20,
+            Direction::Minus => 0x21,
+        };
+        self.write_pipe(0, command_word(axis, code))
+            .inspect(|_| self.mark_continuous(axis.into(), false))
+            .during("fixed_drive", axis.one())
+    }
+
+    /// 向指定方向连续驱动，直到停止命令或限位
+    pub fn continuous_drive(&self, axis: OneOrAllAxis, direction: Direction) -> Result<(), Error> {
+        let code = match direction {
+            Direction::Plus => 0x22,
+            Direction::Minus => 0x23,
+        };
+        self.write_pipe(0, command_word(axis, code))
+            .inspect(|_| self.mark_continuous(axis.into(), true))
+            .during("continuous_drive", axis.one())
+    }
+
+    /// 记录 `axes` 最近一次发出的驱动命令是否为连续驱动
+    pub(crate) fn mark_continuous(&self, axes: Axes, continuous: bool) {
+        let marked = self.continuous.get();
+        self.continuous.set(if continuous {
+            marked | axes
+        } else {
+            Axes::from_bits(marked.bits() & !axes.bits())
+        });
+    }
+}
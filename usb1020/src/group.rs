@@ -0,0 +1,165 @@
+use crate::common::{Axes, Axis, Direction};
+use crate::config::{axis_key, ConfigError, DriveConfig};
+use crate::wait::WaitOptions;
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+use std::time::Duration;
+
+/// 驱动开始保持（命令码 0x24），保持期间发出的驱动命令暂不执行
+const DRIVE_HOLD: u8 = 0x24;
+/// 解除驱动开始保持（命令码 0x25），被保持的驱动同时开始
+const DRIVE_RELEASE: u8 = 0x25;
+/// 立即停止
+const SUDDEN_STOP: u8 = 0x27;
+
+/// 一个轴的独立（非插补）定长驱动
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisMove {
+    pub axis: Axis,
+    /// 速度曲线参数
+    pub drive: DriveConfig,
+    /// 输出脉冲数 P
+    pub pulses: u32,
+    pub direction: Direction,
+}
+
+/// 检查一组驱动，返回涉及的轴
+pub(crate) fn validate(moves: &[AxisMove]) -> Result<Axes, Error> {
+    let mut axes = Axes::NONE;
+    let mut errors = Vec::new();
+    for m in moves {
+        let mut error = |key: &str, message: String| {
+            errors.push(ConfigError {
+                key: format!("axis.{}{}", axis_key(m.axis), key),
+                line: None,
+                message,
+            })
+        };
+        if axes.contains(m.axis) {
+            error("", "appears more than once".to_owned());
+        }
+        for (key, message) in m.drive.problems() {
+            error(&format!(".drive.{}", key), message);
+        }
+        axes = axes | m.axis;
+    }
+    if errors.is_empty() {
+        Ok(axes)
+    } else {
+        Err(Error::InvalidConfig(errors))
+    }
+}
+
+/// 已装载、被保持的一组驱动，由 [`USB1020Controller::prepare_moves`] 得到。
+///
+/// [`start`](Self::start) 以一条带轴掩码的命令解除保持，各轴在同一芯片周期开始驱动。
+/// 未调用 `start` 即被丢弃时，立即停止这些轴并解除保持，驱动不会开始。
+#[derive(Debug)]
+#[must_use = "the moves do not start until `start` is called"]
+pub struct PreparedMoves<'a, T: UsbContext> {
+    controller: &'a USB1020Controller<T>,
+    axes: Axes,
+    started: bool,
+}
+
+impl<'a, T: UsbContext> PreparedMoves<'a, T> {
+    /// 参与驱动的轴
+    pub fn axes(&self) -> Axes {
+        self.axes
+    }
+
+    /// 同时开始所有驱动，返回的 [`StartedMoves`] 可用于等待驱动结束
+    pub fn start(mut self) -> Result<StartedMoves<'a, T>, Error> {
+        let mut transaction = self.controller.transaction();
+        transaction.command(self.axes, DRIVE_RELEASE);
+        transaction.flush().during("start_moves", None)?;
+        self.started = true;
+        Ok(StartedMoves {
+            controller: self.controller,
+            axes: self.axes,
+        })
+    }
+}
+
+impl<T: UsbContext> Drop for PreparedMoves<'_, T> {
+    fn drop(&mut self) {
+        if self.started {
+            return;
+        }
+        // 先停止再解除保持，被保持的驱动不会开始
+        let mut transaction = self.controller.transaction();
+        transaction
+            .command(self.axes, SUDDEN_STOP)
+            .command(self.axes, DRIVE_RELEASE);
+        let _ = transaction.flush();
+    }
+}
+
+/// 已同时开始的一组驱动
+#[derive(Debug)]
+pub struct StartedMoves<'a, T: UsbContext> {
+    controller: &'a USB1020Controller<T>,
+    axes: Axes,
+}
+
+impl<T: UsbContext> StartedMoves<'_, T> {
+    /// 参与驱动的轴
+    pub fn axes(&self) -> Axes {
+        self.axes
+    }
+
+    /// 等待所有轴结束驱动，见 [`wait_idle`](USB1020Controller::wait_idle)
+    pub fn wait(&self, timeout: Duration, options: &WaitOptions) -> Result<(), Error> {
+        self.controller.wait_idle(self.axes, timeout, options)
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 装载各轴的速度曲线与输出脉冲数并发出驱动命令，但保持不开始。
+    ///
+    /// 全部写入在一个事务中完成；参数不合法或同一轴出现多次时不写入任何内容。
+    pub fn prepare_moves(&self, moves: &[AxisMove]) -> Result<PreparedMoves<'_, T>, Error> {
+        let apply = || {
+            let axes = validate(moves)?;
+            let mut modes = self.modes.get();
+            let mut ranges = self.ranges.get();
+
+            let mut transaction = self.transaction();
+            transaction.command(axes, DRIVE_HOLD);
+            for m in moves {
+                let index = m.axis.index();
+                modes[index].wr3 = m.drive.queue(&mut transaction, m.axis, modes[index].wr3);
+                ranges[index] = m.drive.range;
+                let code = match m.direction {
+                    Direction::Plus => 0x20,
+                    Direction::Minus => 0x21,
+                };
+                transaction
+                    .write_data(m.axis, 0x06, m.pulses)
+                    .command(m.axis, code);
+            }
+            if let Err(e) = transaction.flush() {
+                // 可能已有部分驱动命令被保持，尽力撤销
+                let mut undo = self.transaction();
+                undo.command(axes, SUDDEN_STOP).command(axes, DRIVE_RELEASE);
+                let _ = undo.flush();
+                return Err(e);
+            }
+
+            self.modes.set(modes);
+            self.ranges.set(ranges);
+            self.mark_continuous(axes, false);
+            Ok(PreparedMoves {
+                controller: self,
+                axes,
+                started: false,
+            })
+        };
+        apply().during("prepare_moves", None)
+    }
+
+    /// 装载并同时开始一组驱动，见 [`prepare_moves`](Self::prepare_moves)
+    pub fn start_moves(&self, moves: &[AxisMove]) -> Result<StartedMoves<'_, T>, Error> {
+        self.prepare_moves(moves)?.start()
+    }
+}
@@ -1,33 +1,112 @@
-use crate::common::Axis;
+use crate::common::{Axes, Axis, Direction};
 use crate::register::*;
-use crate::Error::{DecodeError, DeviceNotFound, USBError};
-use common::OneOrAllAxis;
-use rusb::{Device, DeviceHandle, GlobalContext};
+use crate::Error::{DecodeError, DeviceNotFound, InvalidDevicePath, ReadNot8Bytes, USBError};
+use common::{DropPolicy, OneOrAllAxis};
+use config::ConfigError;
+use device::{DeviceInfo, OpenOptions};
+use policy::UsbPolicy;
+use rusb::{Device, DeviceHandle, GlobalContext, UsbContext};
+use std::cell::Cell;
 use std::time::Duration;
+use transaction::Transaction;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
 pub mod common;
+pub mod config;
+pub mod device;
+pub mod drive;
+pub mod group;
+pub mod planner;
+pub mod policy;
+pub mod probe;
+pub mod profile;
 pub mod register;
+pub mod scale;
+pub mod shared;
+pub mod snapshot;
+pub mod supervisor;
+pub mod sync;
+pub mod transaction;
+pub mod wait;
 
 #[cfg(test)]
 mod tests;
 
 #[derive(Debug, PartialEq)]
-pub struct USB1020Controller {
-    device_handle: DeviceHandle<GlobalContext>,
+pub struct USB1020Controller<T: UsbContext = GlobalContext> {
+    device_handle: DeviceHandle<T>,
+    /// 端点 0x88 上可能残留着未读走的应答包，下次读取前需要先排空
+    stale: Cell<bool>,
+    /// 普通命令与读寄存器使用的传输策略
+    policy: UsbPolicy,
+    /// 停止命令使用的传输策略
+    stop_policy: UsbPolicy,
+    /// 关闭时对各轴采取的动作
+    drop_policy: DropPolicy,
+    /// 打开时卸载了接口 0 上的内核驱动，关闭时需要重新挂载
+    reattach_kernel_driver: bool,
+    /// 已经执行过关闭流程
+    closed: bool,
+    /// 按 X、Y、Z、U 顺序记录的各轴模式寄存器
+    modes: Cell<[ModeRegisters; 4]>,
+    /// 按 X、Y、Z、U 顺序记录的各轴倍率 R
+    ranges: Cell<[u32; 4]>,
+    /// 按 X、Y、Z、U 顺序记录的各轴最近一次读到或写入的 (LP, EP)
+    positions: Cell<[KnownPosition; 4]>,
+    /// 最近一次由本库发出的驱动命令为连续驱动的轴
+    continuous: Cell<Axes>,
 }
 
-// impl Drop for USB1020Controller {
-//     fn drop(&mut self) {
-//         drop(self.device)
-//         // todo!()
-//     }
-// }
+/// 某个轴最近一次读到或写入的 LP 与 EP，未知时为 `None`
+pub(crate) type KnownPosition = (Option<u32>, Option<u32>);
+
+impl<T: UsbContext> Drop for USB1020Controller<T> {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
 
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum Error {
     #[error("internal usb error: {0}")]
     USBError(#[from] rusb::Error),
 
+    #[error("usb transfer failed while {phase} register {register}: {source}")]
+    Transfer {
+        phase: Phase,
+        /// 写入时为管道号，读取时为读寄存器号
+        register: u8,
+        source: rusb::Error,
+    },
+
+    #[error("{operation}{} failed: {source}", on_axis(.axis))]
+    Operation {
+        operation: &'static str,
+        axis: Option<Axis>,
+        source: Box<Error>,
+    },
+
+    #[error("{axis:?} axis stopped by {} {direction:?} limit", if *.software { "software" } else { "hardware" })]
+    LimitHit {
+        axis: Axis,
+        direction: Direction,
+        /// 软件限位（COMP+/COMP-）而非外部限位信号
+        software: bool,
+    },
+
+    #[error("{axis:?} axis servo alarm")]
+    ServoAlarm { axis: Axis },
+
+    #[error("{axis:?} axis stopped by emergency stop")]
+    EmergencyStop { axis: Axis },
+
+    #[error("{axis:?} axis homing failed at {step:?}")]
+    HomingFailed {
+        axis: Axis,
+        step: AutoHomeSearchStep,
+    },
+
     #[error("read not 8 bytes?")]
     ReadNot8Bytes,
 
@@ -36,93 +115,409 @@ pub enum Error {
 
     #[error("no suitable device")]
     DeviceNotFound,
+
+    #[error("device disconnected")]
+    Disconnected,
+
+    #[error("timed out after {0:?}")]
+    WaitTimeout(Duration),
+
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("permission denied opening the device; on Linux add a udev rule granting access to 04b4:1020, on Windows install WinUSB for the card")]
+    PermissionDenied,
+
+    #[error("device is busy: interface 0 is claimed by another process or a kernel driver; close other programs using the card or allow detaching the kernel driver")]
+    DeviceBusy,
+
+    #[error("device is in configuration {active:?} and switching to configuration 1 failed; replug the card or stop other programs using it")]
+    WrongConfiguration { active: Option<u8> },
+
+    #[error("invalid device path {0:?}, expected something like \"1-3.2\"")]
+    InvalidDevicePath(String),
+
+    #[error("failed to parse machine config: {0}")]
+    ConfigSyntax(String),
+
+    #[error("invalid machine config: {}", config::describe(.0))]
+    InvalidConfig(Vec<ConfigError>),
+
+    #[error("{quantity} {value} is out of range for the chip registers")]
+    OutOfRange { quantity: &'static str, value: f64 },
+
+    #[error("no range multiplier satisfies both {first} and {second}")]
+    IncompatibleSpeeds {
+        first: &'static str,
+        second: &'static str,
+    },
+
+    #[error("{axis:?} axis cannot run both {first:?} and {second:?} as sync actions")]
+    ConflictingSyncActions {
+        axis: Axis,
+        first: sync::Action,
+        second: sync::Action,
+    },
+
+    #[error(
+        "{provocation:?} on {trigger:?} axis would also run {action:?} on {target:?} axis, \
+         because sync actions on one trigger axis share every provocation and action"
+    )]
+    UnintendedSyncAction {
+        trigger: Axis,
+        provocation: sync::Provocation,
+        target: Axis,
+        action: sync::Action,
+    },
+
+    #[error("{axis:?} axis finished probing without the probe triggering")]
+    ProbeNotTriggered { axis: Axis },
+}
+
+/// 各轴模式寄存器 WR1～WR3 最近一次写入的值。
+///
+/// 芯片不能读回模式寄存器，只修改其中几位时须据此拼出完整的字再写入。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ModeRegisters {
+    pub wr1: u16,
+    pub wr2: u16,
+    pub wr3: u16,
+}
+
+impl ModeRegisters {
+    /// 初始化时写入的值
+    const INIT: Self = Self {
+        wr1: 0x0000,
+        wr2: 0x0100,
+        wr3: 0x0f00,
+    };
+}
+
+/// 一次管道写入或寄存器读取所处的 USB 传输阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// 向端点 0x02 写入管道号或读寄存器号
+    WriteSelect,
+    /// 向端点 0x04 写入数据
+    WriteData,
+    /// 从端点 0x88 读取应答
+    Read,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::WriteSelect => "selecting",
+            Phase::WriteData => "writing",
+            Phase::Read => "reading",
+        })
+    }
+}
+
+fn on_axis(axis: &Option<Axis>) -> String {
+    axis.map(|axis| format!(" on {:?} axis", axis))
+        .unwrap_or_default()
+}
+
+/// 为错误附加所执行的操作与轴
+trait During<R> {
+    fn during(self, operation: &'static str, axis: Option<Axis>) -> Result<R, Error>;
 }
 
-impl USB1020Controller {
+impl<R> During<R> for Result<R, Error> {
+    fn during(self, operation: &'static str, axis: Option<Axis>) -> Result<R, Error> {
+        self.map_err(|source| Error::Operation {
+            operation,
+            axis,
+            source: Box::new(source),
+        })
+    }
+}
+
+impl Error {
+    fn transfer(phase: Phase, register: u8, source: rusb::Error) -> Self {
+        Error::Transfer {
+            phase,
+            register,
+            source,
+        }
+    }
+
+    /// 去掉 [`Error::Operation`] 包装后的根本原因
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::Operation { source, .. } => source.cause(),
+            e => e,
+        }
+    }
+
+    /// 底层的 USB 错误，可用于判断设备是否已断开（[`rusb::Error::NoDevice`]）
+    pub fn usb_error(&self) -> Option<rusb::Error> {
+        match self.cause() {
+            USBError(e) | Error::Transfer { source: e, .. } => Some(*e),
+            _ => None,
+        }
+    }
+
+    /// 区分打开、声明设备时常见的权限与占用错误
+    fn from_open(error: rusb::Error) -> Self {
+        match error {
+            rusb::Error::Access => Error::PermissionDenied,
+            rusb::Error::Busy => Error::DeviceBusy,
+            e => USBError(e),
+        }
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
     const VENDOR_ID: u16 = 0x04b4;
     const PRODUCT_ID: u16 = 0x1020;
 
-    const USB_TIMEOUT: Duration = Duration::from_millis(100);
+    /// 排空端点 0x88 时单次读取的超时，读到超时即认为已无残留
+    const DRAIN_TIMEOUT: Duration = Duration::from_millis(10);
+    /// 排空时最多丢弃的包数，防止设备持续发送时陷入死循环
+    const DRAIN_LIMIT: usize = 64;
 
-    pub fn device_handle(&self) -> &DeviceHandle<GlobalContext> {
+    pub fn device_handle(&self) -> &DeviceHandle<T> {
         &self.device_handle
     }
 
-    pub fn fast_open() -> Result<Self, Error> {
-        let mut device_handle = rusb::open_device_with_vid_pid(Self::VENDOR_ID, Self::PRODUCT_ID)
+    pub fn policy(&self) -> &UsbPolicy {
+        &self.policy
+    }
+
+    /// 设置普通命令与读寄存器使用的传输策略
+    pub fn set_policy(&mut self, policy: UsbPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn stop_policy(&self) -> &UsbPolicy {
+        &self.stop_policy
+    }
+
+    /// 设置 [`decel_stop`](Self::decel_stop) 与 [`sudden_stop`](Self::sudden_stop) 使用的传输策略
+    pub fn set_stop_policy(&mut self, policy: UsbPolicy) {
+        self.stop_policy = policy;
+    }
+
+    pub fn drop_policy(&self) -> DropPolicy {
+        self.drop_policy
+    }
+
+    /// 设置关闭时对各轴采取的动作，默认减速停止
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.drop_policy = policy;
+    }
+
+    /// 按 [`drop_policy`](Self::drop_policy) 停止各轴，释放接口 0，并重新挂载打开时卸载的内核驱动。
+    ///
+    /// 控制器被 drop 时会执行同样的流程，但无法报告其中的错误。
+    pub fn close(mut self) -> Result<(), Error> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+
+        let stopped = match self.drop_policy {
+            DropPolicy::SuddenStop => self.sudden_stop(OneOrAllAxis::All),
+            DropPolicy::DecelStop => self.decel_stop(OneOrAllAxis::All),
+            DropPolicy::LeaveRunning => Ok(()),
+        };
+        // 停止失败时仍然释放接口
+        let released = self.device_handle.release_interface(0).map_err(USBError);
+        let reattached = if self.reattach_kernel_driver {
+            self.device_handle.attach_kernel_driver(0).map_err(USBError)
+        } else {
+            Ok(())
+        };
+        stopped.and(released).and(reattached)
+    }
+
+    /// 在给定的 libusb 上下文中打开找到的第一块 USB1020
+    pub fn fast_open_with(context: &T) -> Result<Self, Error> {
+        let device = context
+            .devices()?
+            .iter()
+            .find(Self::device_filter)
             .ok_or(DeviceNotFound)?;
-        device_handle.set_active_configuration(1)?;
-        device_handle.claim_interface(0)?;
+        Self::from_handle(device.open().map_err(Error::from_open)?)
+    }
 
-        let this = Self { device_handle };
-        this.init_device()?;
+    /// 在给定的 libusb 上下文中打开 [`device::list_devices_with`] 列出的某一块 USB1020
+    pub fn open_with(context: &T, info: &DeviceInfo) -> Result<Self, Error> {
+        let device = context
+            .devices()?
+            .iter()
+            .find(|device| Self::device_filter(device) && info.matches(device))
+            .ok_or(DeviceNotFound)?;
+        Self::from_handle(device.open().map_err(Error::from_open)?)
+    }
 
-        Ok(this)
+    /// 在给定的 libusb 上下文中按形如 `1-3.2` 的端口路径打开 USB1020
+    pub fn open_by_path_with(context: &T, path: &str) -> Result<Self, Error> {
+        let (bus, port_path) =
+            DeviceInfo::parse_path(path).ok_or_else(|| InvalidDevicePath(path.to_owned()))?;
+        let device = context
+            .devices()?
+            .iter()
+            .find(|device| {
+                Self::device_filter(device)
+                    && device.bus_number() == bus
+                    && matches!(device.port_numbers(), Ok(ports) if ports == port_path)
+            })
+            .ok_or(DeviceNotFound)?;
+        Self::from_handle(device.open().map_err(Error::from_open)?)
     }
 
-    fn init_device(&self) -> Result<(), Error> {
-        self.write_pipe(0, 0x8000)?;
+    /// 在给定的 libusb 上下文中按序列号打开 USB1020
+    pub fn open_by_serial_with(context: &T, serial: &str) -> Result<Self, Error> {
+        let info = device::list_devices_with(context)?
+            .into_iter()
+            .find(|info| info.serial.as_deref() == Some(serial))
+            .ok_or(DeviceNotFound)?;
+        Self::open_with(context, &info)
+    }
 
-        self.write_pipe(0, 0x010f)?;
-        self.write_pipe(1, 0x0000)?;
-        self.write_pipe(2, 0x0100)?; // TODO: X_PULSE_BF
-        self.write_pipe(3, 0x0f00)?;
+    fn from_handle(device_handle: DeviceHandle<T>) -> Result<Self, Error> {
+        Self::from_handle_with(device_handle, &OpenOptions::default())
+    }
 
-        self.write_pipe(0, 0x020f)?;
-        self.write_pipe(1, 0x0000)?;
-        self.write_pipe(2, 0x0100)?; // TODO: Y_PULSE_BF
-        self.write_pipe(3, 0x0f00)?;
+    /// 由已打开的设备句柄构造控制器：按需卸载内核驱动，切换到配置 1，声明接口 0 并初始化设备
+    pub fn from_handle_with(
+        device_handle: DeviceHandle<T>,
+        options: &OpenOptions,
+    ) -> Result<Self, Error> {
+        let detached = options.detach_kernel_driver
+            && matches!(device_handle.kernel_driver_active(0), Ok(true));
+        if detached {
+            device_handle
+                .detach_kernel_driver(0)
+                .map_err(Error::from_open)?;
+        }
+
+        if let Err(e) = Self::claim(&device_handle) {
+            if detached {
+                let _ = device_handle.attach_kernel_driver(0);
+            }
+            return Err(e);
+        }
+
+        let this = Self {
+            device_handle,
+            stale: Cell::new(false),
+            policy: UsbPolicy::default(),
+            stop_policy: UsbPolicy::critical(),
+            drop_policy: DropPolicy::default(),
+            reattach_kernel_driver: detached,
+            closed: false,
+            modes: Cell::new([ModeRegisters::INIT; 4]),
+            ranges: Cell::new([drive::DEFAULT_RANGE; 4]),
+            positions: Cell::new([(None, None); 4]),
+            continuous: Cell::new(Axes::NONE),
+        };
+        this.init_device().during("init_device", None)?;
 
-        self.write_pipe(0, 0x040f)?;
-        self.write_pipe(1, 0x0000)?;
-        self.write_pipe(2, 0x0100)?; // TODO: Z_PULSE_BF
-        self.write_pipe(3, 0x0f00)?;
+        Ok(this)
+    }
 
-        self.write_pipe(0, 0x080f)?;
-        self.write_pipe(1, 0x0000)?;
-        self.write_pipe(2, 0x0100)?; // TODO: U_PULSE_BF
-        self.write_pipe(3, 0x0f00)?;
+    fn claim(device_handle: &DeviceHandle<T>) -> Result<(), Error> {
+        let active = device_handle.active_configuration().ok();
+        if active != Some(1) {
+            device_handle
+                .set_active_configuration(1)
+                .map_err(|e| match e {
+                    rusb::Error::Access | rusb::Error::Busy => Error::from_open(e),
+                    _ => Error::WrongConfiguration { active },
+                })?;
+        }
+        device_handle.claim_interface(0).map_err(Error::from_open)
+    }
 
-        self.write_pipe(4, 0xffff)?;
-        self.write_pipe(5, 0)?;
-        self.set_lp(OneOrAllAxis::All, 0)?;
-        self.set_ep(OneOrAllAxis::All, 0)?;
+    fn init_device(&self) -> Result<(), Error> {
+        let mut transaction = self.transaction();
+        transaction.write_pipe(0, 0x8000);
+
+        let modes = ModeRegisters::INIT;
+        for axis in Axis::ALL {
+            transaction
+                .select(axis)
+                .write_pipe(1, modes.wr1)
+                .write_pipe(2, modes.wr2) // TODO: PULSE_BF
+                .write_pipe(3, modes.wr3);
+        }
+
+        transaction
+            .write_pipe(4, 0xffff)
+            .write_pipe(5, 0)
+            .set_u32_data(0)
+            .write_pipe(0, 0x0f09) // LP
+            .set_u32_data(0)
+            .write_pipe(0, 0x0f0a) // EP
+            .write_data(Axes::ALL, 0x00, drive::DEFAULT_RANGE); // R
+        transaction.flush()?;
+        self.modes.set([modes; 4]);
+        self.ranges.set([drive::DEFAULT_RANGE; 4]);
 
         Ok(())
     }
 
-    pub fn device_filter(device: &Device<GlobalContext>) -> bool {
+    /// 开始一个批量命令事务，见 [`Transaction`]
+    pub fn transaction(&self) -> Transaction<'_, T> {
+        Transaction::new(self)
+    }
+
+    pub fn device_filter(device: &Device<T>) -> bool {
         matches!(device.device_descriptor(), Ok(desc)
             if desc.vendor_id() == Self::VENDOR_ID && desc.product_id() == Self::PRODUCT_ID)
     }
 
-    fn bulk_write_0x02(&self, buf: &[u8]) -> Result<usize, Error> {
-        self.device_handle
-            .write_bulk(0x02, buf, Self::USB_TIMEOUT)
-            .map_err(USBError)
+    fn bulk_write_0x02(&self, policy: &UsbPolicy, buf: &[u8]) -> Result<usize, Error> {
+        policy
+            .run_write(
+                |timeout| self.device_handle.write_bulk(0x02, buf, timeout),
+                || self.device_handle.clear_halt(0x02),
+            )
+            .map_err(|source| Error::transfer(Phase::WriteSelect, buf[0], source))
     }
 
-    fn bulk_write_0x04(&self, buf: &[u8]) -> Result<usize, Error> {
-        self.device_handle
-            .write_bulk(0x04, buf, Self::USB_TIMEOUT)
-            .map_err(USBError)
+    fn bulk_write_0x04(&self, policy: &UsbPolicy, pipe: u8, buf: &[u8]) -> Result<usize, Error> {
+        policy
+            .run_write(
+                |timeout| self.device_handle.write_bulk(0x04, buf, timeout),
+                || self.device_handle.clear_halt(0x04),
+            )
+            .map_err(|source| Error::transfer(Phase::WriteData, pipe, source))
     }
 
     fn write_pipe(&self, pipe: u8, data: u16) -> Result<(), Error> {
+        self.write_pipe_with(&self.policy, pipe, data)
+    }
+
+    /// 写入一个管道：先向端点 0x02 发送管道号，再向端点 0x04 发送数据。
+    ///
+    /// 任一半超时即整体失败，不单独重发另一半，以免管道号与数据错位。
+    fn write_pipe_with(&self, policy: &UsbPolicy, pipe: u8, data: u16) -> Result<(), Error> {
         let mut buf = [0u8; 512];
         buf[0] = pipe;
-        self.bulk_write_0x02(buf.as_ref())?;
+        self.bulk_write_0x02(policy, buf.as_ref())?;
         let [data_0, data_1] = data.to_le_bytes();
         buf[0] = data_0;
         buf[1] = data_1;
-        self.bulk_write_0x04(buf.as_ref())?;
+        self.bulk_write_0x04(policy, pipe, buf.as_ref())?;
         Ok(())
     }
 
-    fn bulk_read_0x88(&self, buf: &mut [u8]) -> Result<usize, Error> {
-        self.device_handle
-            .read_bulk(0x88, buf, Self::USB_TIMEOUT)
-            .map_err(USBError)
+    fn bulk_read_0x88(&self, rr_no: u8, buf: &mut [u8]) -> Result<usize, Error> {
+        self.policy
+            .run(
+                |timeout| self.device_handle.read_bulk(0x88, buf, timeout),
+                || self.device_handle.clear_halt(0x88),
+            )
+            .map_err(|source| Error::transfer(Phase::Read, rr_no, source))
     }
 
     //    fn bulk_read_0x86(&self, buf: &mut [u8]) -> Result<usize, Error> {
@@ -131,49 +526,120 @@ impl USB1020Controller {
     //            .map_err(USBError)
     //    }
 
+    /// 读取端点 0x88 上的一个应答包，应答固定为 8 字节，寄存器值位于前两字节
+    fn read_response(&self, rr_no: u8) -> Result<[u8; 2], Error> {
+        let mut buf = [0u8; 8];
+        match self.bulk_read_0x88(rr_no, buf.as_mut())? {
+            8 => Ok([buf[0], buf[1]]),
+            _ => Err(ReadNot8Bytes),
+        }
+    }
+
+    /// 丢弃端点 0x88 上所有残留的应答包，直到读取超时为止
+    fn drain_0x88(&self, rr_no: u8) -> Result<(), Error> {
+        let mut buf = [0u8; 512];
+        for _ in 0..Self::DRAIN_LIMIT {
+            match self
+                .device_handle
+                .read_bulk(0x88, buf.as_mut(), Self::DRAIN_TIMEOUT)
+            {
+                Ok(_) => continue,
+                Err(rusb::Error::Timeout) => {
+                    self.stale.set(false);
+                    return Ok(());
+                }
+                Err(source) => return Err(Error::transfer(Phase::Read, rr_no, source)),
+            }
+        }
+        Err(Error::transfer(Phase::Read, rr_no, rusb::Error::Overflow))
+    }
+
+    /// 选择读寄存器并取回其值。
+    ///
+    /// 固件在每次选择后先回送上一次锁存的旧值，再回送本次选择的寄存器，
+    /// 因此一次读取固定消耗两个应答包。若上一次读取中途失败，端点上可能
+    /// 残留多余的包，使之后的每次读取都错开一个寄存器；此时先排空端点再读。
+    /// 读取超时后同样排空端点并重试一次。
     fn pull_rr(&self, rr_no: u8) -> Result<[u8; 2], Error> {
+        match self.try_pull_rr(rr_no) {
+            Err(Error::Transfer {
+                source: rusb::Error::Timeout,
+                ..
+            }) => {
+                self.drain_0x88(rr_no)?;
+                self.try_pull_rr(rr_no)
+            }
+            result => result,
+        }
+    }
+
+    fn try_pull_rr(&self, rr_no: u8) -> Result<[u8; 2], Error> {
+        if self.stale.get() {
+            self.drain_0x88(rr_no)?;
+        }
+
         let mut buf = [0u8; 512];
         buf[0] = rr_no;
-        self.bulk_write_0x02(buf.as_ref())?;
-
-        let mut buf = [0u8; 8];
-        self.bulk_read_0x88(buf.as_mut())?; // dispose for some reason
-        self.bulk_read_0x88(buf.as_mut())?;
-        Ok([buf[0], buf[1]])
+        self.bulk_write_0x02(&self.policy, buf.as_ref())?;
+
+        // 两个应答包都读完之前，端点上的数据都视为不可信
+        self.stale.set(true);
+        self.read_response(rr_no)?; // 上一次锁存的旧值
+        let data = self.read_response(rr_no)?;
+        self.stale.set(false);
+        Ok(data)
     }
 
     pub fn get_rr0(&self) -> Result<MainStatusRegister, Error> {
-        let buf = self.pull_rr(0)?;
-        MainStatusRegister::try_from(buf.as_ref()).map_err(DecodeError)
+        self.pull_rr(0)
+            .and_then(|buf| MainStatusRegister::try_from(buf.as_ref()).map_err(DecodeError))
+            .during("get_rr0", None)
     }
 
     pub fn get_rr1(&self, axis: Axis) -> Result<AxisStatusRegister, Error> {
-        self.write_pipe(0, axis.as_data())?;
-        let buf = self.pull_rr(1)?;
-        AxisStatusRegister::try_from(buf.as_ref()).map_err(DecodeError)
+        self.write_pipe(0, axis.as_data())
+            .and_then(|_| self.pull_rr(1))
+            .and_then(|buf| AxisStatusRegister::try_from(buf.as_ref()).map_err(DecodeError))
+            .during("get_rr1", Some(axis))
     }
 
     pub fn get_rr2(&self, axis: Axis) -> Result<AxisErrorRegister, Error> {
-        self.write_pipe(0, axis.as_data())?;
-        let buf = self.pull_rr(2)?;
-        AxisErrorRegister::try_from(buf.as_ref()).map_err(DecodeError)
+        self.write_pipe(0, axis.as_data())
+            .and_then(|_| self.pull_rr(2))
+            .and_then(|buf| AxisErrorRegister::try_from(buf.as_ref()).map_err(DecodeError))
+            .during("get_rr2", Some(axis))
     }
 
     pub fn get_rr3(&self) -> Result<RR3, Error> {
-        let buf = self.pull_rr(4)?;
-        RR3::try_from(buf.as_ref()).map_err(DecodeError)
+        self.pull_rr(4)
+            .and_then(|buf| RR3::try_from(buf.as_ref()).map_err(DecodeError))
+            .during("get_rr3", None)
     }
 
     pub fn get_rr4(&self) -> Result<RR4, Error> {
-        let buf = self.pull_rr(5)?;
-        RR4::try_from(buf.as_ref()).map_err(DecodeError)
+        self.pull_rr(5)
+            .and_then(|buf| RR4::try_from(buf.as_ref()).map_err(DecodeError))
+            .during("get_rr4", None)
+    }
+    /// 读取指定轴的外部输入信号，X、Y 轴来自 RR3，Z、U 轴来自 RR4
+    pub fn get_signals(&self, axis: Axis) -> Result<AxisExternalSignal, Error> {
+        Ok(match axis {
+            Axis::X => self.get_rr3()?.x,
+            Axis::Y => self.get_rr3()?.y,
+            Axis::Z => self.get_rr4()?.z,
+            Axis::U => self.get_rr4()?.u,
+        })
     }
+
     /// 读取中断寄存器 RR5 并将其置为 0
     pub fn get_rr5(&self, axis: Axis) -> Result<RR5, Error> {
-        self.write_pipe(0, axis.as_data())?;
-        let buf = self.pull_rr(3)?;
-        self.write_pipe(0, 0x45)?; // TODO: 此处是否清除了 RR5 内容？
-        RR5::try_from(buf.as_ref()).map_err(DecodeError)
+        self.write_pipe(0, axis.as_data())
+            .and_then(|_| self.pull_rr(3))
+            .and_then(|buf| {
+                self.write_pipe(0, 0x45)?; // TODO: 此处是否清除了 RR5 内容？
+                RR5::try_from(buf.as_ref()).map_err(DecodeError)
+            })
+            .during("get_rr5", Some(axis))
     }
 
     pub fn read_br(&self, axis: Axis) -> Result<u32, Error> {
@@ -185,9 +651,9 @@ impl USB1020Controller {
                 Axis::Z => 0x0414,
                 Axis::U => 0x0814,
             },
-        )?;
-
-        self.read_u32_data()
+        )
+        .and_then(|_| self.read_u32_data())
+        .during("read_br", Some(axis))
     }
 
     pub fn read_ca(&self, axis: Axis) -> Result<u16, Error> {
@@ -199,9 +665,9 @@ impl USB1020Controller {
                 Axis::Z => 0x0413,
                 Axis::U => 0x0813,
             },
-        )?;
-
-        self.read_u16_data()
+        )
+        .and_then(|_| self.read_u16_data())
+        .during("read_ca", Some(axis))
     }
 
     pub fn read_cv(&self, axis: Axis) -> Result<u16, Error> {
@@ -213,9 +679,9 @@ impl USB1020Controller {
                 Axis::Z => 0x0412,
                 Axis::U => 0x0812,
             },
-        )?;
-
-        self.read_u16_data()
+        )
+        .and_then(|_| self.read_u16_data())
+        .during("read_cv", Some(axis))
     }
 
     pub fn read_ep(&self, axis: Axis) -> Result<u32, Error> {
@@ -227,9 +693,10 @@ impl USB1020Controller {
                 Axis::Z => 0x0411,
                 Axis::U => 0x0811,
             },
-        )?;
-
-        self.read_u32_data()
+        )
+        .and_then(|_| self.read_u32_data())
+        .inspect(|&ep| self.remember_position(axis, None, Some(ep)))
+        .during("read_ep", Some(axis))
     }
 
     pub fn read_lp(&self, axis: Axis) -> Result<u32, Error> {
@@ -241,9 +708,24 @@ impl USB1020Controller {
                 Axis::Z => 0x0410,
                 Axis::U => 0x0810,
             },
-        )?;
+        )
+        .and_then(|_| self.read_u32_data())
+        .inspect(|&lp| self.remember_position(axis, Some(lp), None))
+        .during("read_lp", Some(axis))
+    }
+
+    /// 记录某个轴的 LP 或 EP，为 `None` 的一项保持不变
+    pub(crate) fn remember_position(&self, axis: Axis, lp: Option<u32>, ep: Option<u32>) {
+        let mut positions = self.positions.get();
+        let known = &mut positions[axis.index()];
+        known.0 = lp.or(known.0);
+        known.1 = ep.or(known.1);
+        self.positions.set(positions);
+    }
 
-        self.read_u32_data()
+    /// 各轴最近一次读到或写入的 (LP, EP)
+    pub(crate) fn known_positions(&self) -> [KnownPosition; 4] {
+        self.positions.get()
     }
 
     fn read_u16_data(&self) -> Result<u16, Error> {
@@ -267,46 +749,117 @@ impl USB1020Controller {
     }
 
     pub fn set_lp(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
-        self.set_u32_data(data)?;
-        self.write_pipe(
-            0,
-            match axis {
-                OneOrAllAxis::One(Axis::X) => 0x0109,
-                OneOrAllAxis::One(Axis::Y) => 0x0209,
-                OneOrAllAxis::One(Axis::Z) => 0x0409,
-                OneOrAllAxis::One(Axis::U) => 0x0809,
-                OneOrAllAxis::All => 0x0f09,
-            },
-        )?;
-        Ok(())
+        self.set_u32_data(data)
+            .and_then(|_| {
+                self.write_pipe(
+                    0,
+                    match axis {
+                        OneOrAllAxis::One(Axis::X) => 0x0109,
+                        OneOrAllAxis::One(Axis::Y) => 0x0209,
+                        OneOrAllAxis::One(Axis::Z) => 0x0409,
+                        OneOrAllAxis::One(Axis::U) => 0x0809,
+                        OneOrAllAxis::All => 0x0f09,
+                    },
+                )
+            })
+            .inspect(|_| {
+                for axis in Axes::from(axis).iter() {
+                    self.remember_position(axis, Some(data), None);
+                }
+            })
+            .during("set_lp", axis.one())
     }
 
     pub fn set_ep(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
-        self.set_u32_data(data)?;
-        self.write_pipe(
+        self.set_u32_data(data)
+            .and_then(|_| {
+                self.write_pipe(
+                    0,
+                    match axis {
+                        OneOrAllAxis::One(Axis::X) => 0x010a,
+                        OneOrAllAxis::One(Axis::Y) => 0x020a,
+                        OneOrAllAxis::One(Axis::Z) => 0x040a,
+                        OneOrAllAxis::One(Axis::U) => 0x080a,
+                        OneOrAllAxis::All => 0x0f0a,
+                    },
+                )
+            })
+            .inspect(|_| {
+                for axis in Axes::from(axis).iter() {
+                    self.remember_position(axis, None, Some(data));
+                }
+            })
+            .during("set_ep", axis.one())
+    }
+    pub fn set_dev_inc_rate(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
+        self.set_u32_data(data)
+            .and_then(|_| {
+                self.write_pipe(
+                    0,
+                    match axis {
+                        OneOrAllAxis::One(Axis::X) => 0x010e,
+                        OneOrAllAxis::One(Axis::Y) => 0x020e,
+                        OneOrAllAxis::One(Axis::Z) => 0x040e,
+                        OneOrAllAxis::One(Axis::U) => 0x080e,
+                        OneOrAllAxis::All => 0x0f0e,
+                    },
+                )
+            })
+            .during("set_dev_inc_rate", axis.one())
+    }
+
+    /// 减速停止，使用 [`stop_policy`](Self::stop_policy) 发送
+    pub fn decel_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.write_pipe_with(
+            &self.stop_policy,
             0,
             match axis {
-                OneOrAllAxis::One(Axis::X) => 0x010a,
-                OneOrAllAxis::One(Axis::Y) => 0x020a,
-                OneOrAllAxis::One(Axis::Z) => 0x040a,
-                OneOrAllAxis::One(Axis::U) => 0x080a,
-                OneOrAllAxis::All => 0x0f0a,
+                OneOrAllAxis::One(Axis::X) => 0x0126,
+                OneOrAllAxis::One(Axis::Y) => 0x0226,
+                OneOrAllAxis::One(Axis::Z) => 0x0426,
+                OneOrAllAxis::One(Axis::U) => 0x0826,
+                OneOrAllAxis::All => 0x0f26,
             },
-        )?;
-        Ok(())
+        )
+        .during("decel_stop", axis.one())
     }
-    pub fn set_dev_inc_rate(&self, axis: OneOrAllAxis, data: u32) -> Result<(), Error> {
-        self.set_u32_data(data)?;
-        self.write_pipe(
+
+    /// 立即停止，使用 [`stop_policy`](Self::stop_policy) 发送
+    pub fn sudden_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.write_pipe_with(
+            &self.stop_policy,
             0,
             match axis {
-                OneOrAllAxis::One(Axis::X) => 0x010e,
-                OneOrAllAxis::One(Axis::Y) => 0x020e,
-                OneOrAllAxis::One(Axis::Z) => 0x040e,
-                OneOrAllAxis::One(Axis::U) => 0x080e,
-                OneOrAllAxis::All => 0x0f0e,
+                OneOrAllAxis::One(Axis::X) => 0x0127,
+                OneOrAllAxis::One(Axis::Y) => 0x0227,
+                OneOrAllAxis::One(Axis::Z) => 0x0427,
+                OneOrAllAxis::One(Axis::U) => 0x0827,
+                OneOrAllAxis::All => 0x0f27,
             },
-        )?;
-        Ok(())
+        )
+        .during("sudden_stop", axis.one())
+    }
+}
+
+/// 使用 libusb 默认上下文的便捷构造函数
+impl USB1020Controller<GlobalContext> {
+    /// 打开找到的第一块 USB1020，连接多块时请使用 [`open`](Self::open) 或 [`open_by_path`](Self::open_by_path)
+    pub fn fast_open() -> Result<Self, Error> {
+        Self::fast_open_with(&GlobalContext::default())
+    }
+
+    /// 打开 [`device::list_devices`] 列出的某一块 USB1020
+    pub fn open(info: &DeviceInfo) -> Result<Self, Error> {
+        Self::open_with(&GlobalContext::default(), info)
+    }
+
+    /// 按形如 `1-3.2` 的端口路径打开 USB1020，插在同一个 USB 口上的设备路径不变
+    pub fn open_by_path(path: &str) -> Result<Self, Error> {
+        Self::open_by_path_with(&GlobalContext::default(), path)
+    }
+
+    /// 按序列号打开 USB1020，设备须提供序列号字符串
+    pub fn open_by_serial(serial: &str) -> Result<Self, Error> {
+        Self::open_by_serial_with(&GlobalContext::default(), serial)
     }
 }
@@ -0,0 +1,235 @@
+use crate::common::{Axis, LineOrCurve};
+use crate::config::{DriveConfig, RANGE, SPEED};
+use crate::{Error, USB1020Controller};
+use rusb::UsbContext;
+
+/// 计算加速度变化率时用到的常数：变化率（pps/s²）= 62.5 × 10⁶ / K × M
+const JERK_FACTOR: f64 = 62.5e6;
+/// 加速度（pps/s）= A × 125 × M
+const ACCELERATION_FACTOR: f64 = 125.0;
+/// K 的上限
+const JERK_MAX: u16 = 65535;
+
+/// 以物理量给出的速度曲线，由 [`SpeedRequest::plan`] 换算为寄存器值
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpeedRequest {
+    /// 初始速度（pps）
+    pub start_speed: f64,
+    /// 驱动速度（pps）
+    pub speed: f64,
+    /// 加速度（pps/s）
+    pub acceleration: f64,
+    /// 减速度（pps/s），为 `None` 时与加速度相同
+    pub deceleration: Option<f64>,
+    /// 加速度变化率（pps/s²），给出时使用 S 曲线加减速
+    pub jerk: Option<f64>,
+}
+
+/// [`SpeedRequest::plan`] 的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedPlan {
+    /// 选定的倍率与各寄存器值
+    pub drive: DriveConfig,
+    /// 按寄存器值实际得到的速度曲线
+    pub actual: SpeedRequest,
+    /// 各项的最大相对量化误差，例如 0.001 表示 0.1%
+    pub error: f64,
+}
+
+impl DriveConfig {
+    /// 本配置实际得到的速度曲线
+    pub fn actual(&self) -> SpeedRequest {
+        let m = self.multiplier();
+        SpeedRequest {
+            start_speed: self.start_speed as f64 * m,
+            speed: self.speed as f64 * m,
+            acceleration: self.acceleration as f64 * ACCELERATION_FACTOR * m,
+            deceleration: self
+                .deceleration
+                .map(|d| d as f64 * ACCELERATION_FACTOR * m),
+            jerk: self.jerk.map(|k| JERK_FACTOR / k as f64 * m),
+        }
+    }
+}
+
+/// 一项物理量及其寄存器值落在范围内时倍率 M 的取值区间
+struct Quantity {
+    name: &'static str,
+    value: f64,
+    min_multiplier: f64,
+    max_multiplier: f64,
+}
+
+impl Quantity {
+    /// 寄存器值 = 物理量 / (M × `factor`)，取 1～8,000
+    fn inverse(name: &'static str, value: f64, factor: f64) -> Self {
+        Self {
+            name,
+            value,
+            min_multiplier: value / (factor * *SPEED.end() as f64),
+            max_multiplier: value / factor,
+        }
+    }
+
+    /// K = M × 62.5 × 10⁶ / 变化率，取 1～65,535
+    fn jerk(value: f64) -> Self {
+        Self {
+            name: "jerk",
+            value,
+            min_multiplier: value / JERK_FACTOR,
+            max_multiplier: JERK_MAX as f64 * value / JERK_FACTOR,
+        }
+    }
+}
+
+impl SpeedRequest {
+    fn quantities(&self) -> Vec<Quantity> {
+        let mut quantities = vec![
+            Quantity::inverse("start speed", self.start_speed, 1.0),
+            Quantity::inverse("speed", self.speed, 1.0),
+            Quantity::inverse("acceleration", self.acceleration, ACCELERATION_FACTOR),
+        ];
+        if let Some(deceleration) = self.deceleration {
+            quantities.push(Quantity::inverse(
+                "deceleration",
+                deceleration,
+                ACCELERATION_FACTOR,
+            ));
+        }
+        if let Some(jerk) = self.jerk {
+            quantities.push(Quantity::jerk(jerk));
+        }
+        quantities
+    }
+
+    /// 倍率 R 下最接近本请求的寄存器值
+    fn drive(&self, range: u32) -> DriveConfig {
+        let m = 8_000_000.0 / range as f64;
+        let speed_max = *SPEED.end() as f64;
+        let speed = |value: f64| (value / m).round().clamp(1.0, speed_max) as u16;
+        let acceleration = |value: f64| {
+            (value / (ACCELERATION_FACTOR * m))
+                .round()
+                .clamp(1.0, speed_max) as u16
+        };
+        let jerk = |value: f64| {
+            (JERK_FACTOR * m / value)
+                .round()
+                .clamp(1.0, JERK_MAX as f64) as u16
+        };
+        DriveConfig {
+            range,
+            start_speed: speed(self.start_speed),
+            speed: speed(self.speed),
+            acceleration: acceleration(self.acceleration),
+            deceleration: self.deceleration.map(acceleration),
+            jerk: self.jerk.map(jerk),
+            curve: if self.jerk.is_some() {
+                LineOrCurve::SCurve
+            } else {
+                LineOrCurve::Line
+            },
+        }
+    }
+
+    /// `actual` 与本请求各项的最大相对误差
+    fn error(&self, actual: &SpeedRequest) -> f64 {
+        let relative = |wanted: f64, got: f64| ((got - wanted) / wanted).abs();
+        let mut error = relative(self.start_speed, actual.start_speed)
+            .max(relative(self.speed, actual.speed))
+            .max(relative(self.acceleration, actual.acceleration));
+        if let (Some(wanted), Some(got)) = (self.deceleration, actual.deceleration) {
+            error = error.max(relative(wanted, got));
+        }
+        if let (Some(wanted), Some(got)) = (self.jerk, actual.jerk) {
+            error = error.max(relative(wanted, got));
+        }
+        error
+    }
+
+    /// 选择倍率 R 与各寄存器值，使各项的最大相对量化误差最小。
+    ///
+    /// 某一项在任何倍率下都超出寄存器范围时返回 [`Error::OutOfRange`]，
+    /// 两项要求的倍率互不相容时返回 [`Error::IncompatibleSpeeds`]。
+    pub fn plan(&self) -> Result<SpeedPlan, Error> {
+        if self.start_speed > self.speed {
+            return Err(Error::IncompatibleSpeeds {
+                first: "start speed",
+                second: "speed",
+            });
+        }
+
+        // R 的取值范围对应 M 的取值范围 1～500
+        let multipliers = 8_000_000.0 / *RANGE.end() as f64..=8_000_000.0 / *RANGE.start() as f64;
+        let mut low = (*multipliers.start(), "range");
+        let mut high = (*multipliers.end(), "range");
+        for quantity in self.quantities() {
+            let (min, max) = (quantity.min_multiplier, quantity.max_multiplier);
+            let in_range = quantity.value.is_finite() && quantity.value > 0.0;
+            if !in_range || min > *multipliers.end() || max < *multipliers.start() {
+                return Err(Error::OutOfRange {
+                    quantity: quantity.name,
+                    value: quantity.value,
+                });
+            }
+            if min > low.0 {
+                low = (min, quantity.name);
+            }
+            if max < high.0 {
+                high = (max, quantity.name);
+            }
+        }
+        if low.0 > high.0 {
+            return Err(Error::IncompatibleSpeeds {
+                first: low.1,
+                second: high.1,
+            });
+        }
+
+        // 候选的 R：M 最小者（速度分辨率最高）、M 为整数者、以及使驱动速度恰好可达者
+        let range_max = ((8_000_000.0 / low.0).floor() as u32).min(*RANGE.end());
+        let range_min = ((8_000_000.0 / high.0).ceil() as u32).max(*RANGE.start());
+        let mut candidates = vec![range_max, range_min];
+        for m in low.0.ceil() as u32..=high.0.floor() as u32 {
+            candidates.push((8_000_000.0 / m as f64).round() as u32);
+        }
+        let registers = (self.speed / high.0).ceil() as u32..=(self.speed / low.0).floor() as u32;
+        for v in registers {
+            candidates.push((8_000_000.0 * v as f64 / self.speed).round() as u32);
+        }
+
+        candidates
+            .into_iter()
+            .filter(|range| (range_min..=range_max).contains(range))
+            .map(|range| {
+                let drive = self.drive(range);
+                let actual = drive.actual();
+                SpeedPlan {
+                    drive,
+                    actual,
+                    error: self.error(&actual),
+                }
+            })
+            .filter(|plan| plan.drive.problems().is_empty())
+            // 误差相同时取 R 大者，即倍率小、速度分辨率高者
+            .min_by(|a, b| {
+                a.error
+                    .total_cmp(&b.error)
+                    .then(b.drive.range.cmp(&a.drive.range))
+            })
+            .ok_or(Error::IncompatibleSpeeds {
+                first: low.1,
+                second: high.1,
+            })
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 规划并写入 `axis` 的速度曲线，规划失败时不写入任何内容
+    pub fn set_speeds(&self, axis: Axis, request: &SpeedRequest) -> Result<SpeedPlan, Error> {
+        let plan = request.plan()?;
+        self.set_drive(axis, &plan.drive)?;
+        Ok(plan)
+    }
+}
@@ -0,0 +1,110 @@
+use std::thread;
+use std::time::Duration;
+
+/// USB 传输的超时与重试策略。
+///
+/// 读取失败于 [`rusb::Error::Timeout`] 或 [`rusb::Error::Pipe`] 时视为暂时性错误，
+/// 按 `backoff`、`2 * backoff`、`4 * backoff`…… 的间隔重试至多 `retries` 次；
+/// 其他错误立即返回。
+///
+/// 写入只在端点 STALL（`Pipe`）且已清除 halt 状态时重试，此时数据未被设备接收。
+/// 超时的写入可能已部分送出，重发会重复写入或使管道号与数据错位，因此不重试，
+/// 整个管道写入以错误返回，由调用方重新同步。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbPolicy {
+    /// 单次传输的超时
+    pub timeout: Duration,
+    /// 暂时性错误的最大重试次数，为 0 时不重试
+    pub retries: u32,
+    /// 首次重试前的等待时间，之后每次加倍
+    pub backoff: Duration,
+    /// 遇到 `Pipe` 错误（端点 STALL）时，重试前先清除端点的 halt 状态
+    pub clear_halt: bool,
+}
+
+impl Default for UsbPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(100),
+            retries: 2,
+            backoff: Duration::from_millis(5),
+            clear_halt: true,
+        }
+    }
+}
+
+impl UsbPolicy {
+    /// 不做任何重试，与早期版本的行为一致
+    pub fn no_retry(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            retries: 0,
+            backoff: Duration::ZERO,
+            clear_halt: false,
+        }
+    }
+
+    /// 用于停止命令的策略：更长的超时、更多的重试和更短的退避，尽最大努力送达。
+    ///
+    /// 与其他写入相同，停止命令超时后不重试。
+    pub fn critical() -> Self {
+        Self {
+            timeout: Duration::from_millis(250),
+            retries: 8,
+            backoff: Duration::from_millis(1),
+            clear_halt: true,
+        }
+    }
+
+    /// 按本策略执行一次读取。
+    ///
+    /// `transfer` 以超时为参数执行传输，`clear_halt` 清除相应端点的 halt 状态。
+    pub(crate) fn run<T>(
+        &self,
+        transfer: impl FnMut(Duration) -> rusb::Result<T>,
+        clear_halt: impl FnMut() -> rusb::Result<()>,
+    ) -> rusb::Result<T> {
+        self.run_if(
+            |error| matches!(error, rusb::Error::Timeout | rusb::Error::Pipe),
+            transfer,
+            clear_halt,
+        )
+    }
+
+    /// 按本策略执行一次写入，只重试能确认未送出任何数据的错误
+    pub(crate) fn run_write<T>(
+        &self,
+        transfer: impl FnMut(Duration) -> rusb::Result<T>,
+        clear_halt: impl FnMut() -> rusb::Result<()>,
+    ) -> rusb::Result<T> {
+        let clear = self.clear_halt;
+        self.run_if(
+            |error| clear && error == rusb::Error::Pipe,
+            transfer,
+            clear_halt,
+        )
+    }
+
+    fn run_if<T>(
+        &self,
+        retryable: impl Fn(rusb::Error) -> bool,
+        mut transfer: impl FnMut(Duration) -> rusb::Result<T>,
+        mut clear_halt: impl FnMut() -> rusb::Result<()>,
+    ) -> rusb::Result<T> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match transfer(self.timeout) {
+                Err(error) if retryable(error) && attempt < self.retries => {
+                    attempt += 1;
+                    if error == rusb::Error::Pipe && self.clear_halt {
+                        clear_halt()?;
+                    }
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+}
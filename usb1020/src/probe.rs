@@ -0,0 +1,189 @@
+use crate::common::{Axis, CounterType, Direction, OneOrAllAxis};
+use crate::config::{check_problems, ActiveLevel, DriveConfig, StopMode};
+use crate::register::AxisStatusRegister;
+use crate::sync::{Action, Provocation, SyncActions, SYNC_SET};
+use crate::wait::WaitOptions;
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+use std::time::Duration;
+
+/// 探测用的外部停止信号。
+///
+/// IN0～IN3 都能使驱动停止，但只有 IN3 能作为同步动作的触发条件，由芯片在触发时刻锁存
+/// 计数器。IN0～IN2 只能在停止后读取位置，其中含有停止距离，见 [`ProbeResult::latched`]。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ProbeInput {
+    In0,
+    In1,
+    In2,
+    /// 芯片可在 IN3 有效的时刻锁存计数器，得到的位置不受停止距离影响
+    In3,
+}
+
+impl ProbeInput {
+    /// WR1 中该信号的有效电平位（D0/D2/D4/D6）与停止允许位（D1/D3/D5/D7）
+    pub(crate) fn wr1_bits(self, level: ActiveLevel) -> (u16, u16) {
+        let shift = 2 * self as u16;
+        let level = match level {
+            ActiveLevel::Low => 0,
+            ActiveLevel::High => 1,
+        };
+        (0b11 << shift, (0b10 | level) << shift)
+    }
+
+    fn triggered(self, status: &AxisStatusRegister) -> bool {
+        match self {
+            ProbeInput::In0 => status.in0,
+            ProbeInput::In1 => status.in1,
+            ProbeInput::In2 => status.in2,
+            ProbeInput::In3 => status.in3,
+        }
+    }
+}
+
+/// 一次探测的参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Probe {
+    /// 探针接入的信号
+    pub input: ProbeInput,
+    /// 探针触发时的电平
+    pub level: ActiveLevel,
+    /// 触发后的停止方式
+    pub stop: StopMode,
+    /// 返回的位置取自哪个计数器
+    pub counter: CounterType,
+    /// 朝向探针的方向
+    pub direction: Direction,
+    /// 探测的速度曲线
+    pub drive: DriveConfig,
+    /// 最多输出的脉冲数，为 `None` 时连续驱动直到触发
+    pub max_distance: Option<u32>,
+}
+
+impl Probe {
+    /// 实际写入的速度曲线：IN 信号在加减速驱动中使驱动减速停止、在定速驱动中使驱动立即
+    /// 停止，因此立即停止时以驱动速度起步
+    pub(crate) fn effective_drive(&self) -> DriveConfig {
+        match self.stop {
+            StopMode::Decel => self.drive,
+            StopMode::Sudden => DriveConfig {
+                start_speed: self.drive.speed,
+                ..self.drive
+            },
+        }
+    }
+}
+
+/// [`USB1020Controller::probe`] 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeResult {
+    /// 探针触发时的位置
+    pub position: i32,
+    /// 位置取自的计数器
+    pub counter: CounterType,
+    /// 位置由芯片在触发时刻锁存（IN3）；为 `false` 时是停止后的位置，含停止距离，
+    /// 减速停止时还含减速距离
+    pub latched: bool,
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 朝探针驱动 `axis`，探针触发后停止并返回触发时的位置。
+    ///
+    /// 探针接在 IN3 上时位置由芯片在触发时刻锁存，接在 IN0～IN2 上时为停止后的位置，
+    /// 见 [`ProbeInput`]。探测期间临时允许该信号的外部停止，结束后恢复 WR1，
+    /// 并清除为 IN3 设定的同步动作。
+    /// 驱动走完 `max_distance` 仍未触发时返回 [`Error::ProbeNotTriggered`]；
+    /// 出错或超时时立即停止该轴。
+    pub fn probe(
+        &self,
+        axis: Axis,
+        probe: &Probe,
+        timeout: Duration,
+        options: &WaitOptions,
+    ) -> Result<ProbeResult, Error> {
+        let drive = probe.effective_drive();
+        check_problems("drive.", drive.problems()).during("probe", Some(axis))?;
+
+        let original = self.modes.get()[axis.index()].wr1;
+        let (mask, bits) = probe.input.wr1_bits(probe.level);
+        let latch = probe.input == ProbeInput::In3;
+
+        let run = || {
+            let mut modes = self.modes.get();
+            let mut transaction = self.transaction();
+            modes[axis.index()].wr3 = drive.queue(&mut transaction, axis, modes[axis.index()].wr3);
+            transaction
+                .select(axis)
+                .write_pipe(1, original & !mask | bits);
+            if latch {
+                let action = match probe.counter {
+                    CounterType::Logic => Action::LatchLogicPosition,
+                    CounterType::Fact => Action::LatchRealPosition,
+                };
+                let actions = SyncActions::new().on(axis, Provocation::In3Rising, axis, action);
+                transaction.write_data(axis, SYNC_SET, actions.word(axis));
+            }
+            let code = match (probe.max_distance, probe.direction) {
+                (Some(pulses), direction) => {
+                    transaction.write_data(axis, 0x06, pulses);
+                    match direction {
+                        Direction::Plus => 0x20,
+                        Direction::Minus => 0x21,
+                    }
+                }
+                (None, Direction::Plus) => 0x22,
+                (None, Direction::Minus) => 0x23,
+            };
+            transaction.command(axis, code);
+            transaction.flush()?;
+            modes[axis.index()].wr1 = original & !mask | bits;
+            self.modes.set(modes);
+            let mut ranges = self.ranges.get();
+            ranges[axis.index()] = drive.range;
+            self.ranges.set(ranges);
+            self.mark_continuous(axis.into(), probe.max_distance.is_none());
+
+            self.wait_idle(axis.into(), timeout, options)?;
+            if !probe.input.triggered(&self.get_rr1(axis)?) {
+                return Err(Error::ProbeNotTriggered { axis });
+            }
+            let position = if latch {
+                self.read_latched(axis)?
+            } else {
+                let position = match probe.counter {
+                    CounterType::Logic => self.read_lp(axis)?,
+                    CounterType::Fact => self.read_ep(axis)?,
+                };
+                position as i32
+            };
+            Ok(ProbeResult {
+                position,
+                counter: probe.counter,
+                latched: latch,
+            })
+        };
+        let result = run();
+        if result.is_err() {
+            let _ = self.sudden_stop(OneOrAllAxis::One(axis));
+        }
+
+        // 恢复 WR1 并清除同步动作
+        let mut transaction = self.transaction();
+        transaction.select(axis).write_pipe(1, original);
+        if latch {
+            transaction.write_data(axis, SYNC_SET, 0);
+        }
+        let restored = transaction.flush();
+        if restored.is_ok() {
+            let mut modes = self.modes.get();
+            modes[axis.index()].wr1 = original;
+            self.modes.set(modes);
+        }
+
+        result
+            .and_then(|result| restored.map(|_| result))
+            .during("probe", Some(axis))
+    }
+}
@@ -0,0 +1,424 @@
+use crate::common::{DecelerationMode, LineOrCurve};
+use crate::config::{check_problems, DriveConfig};
+use crate::Error;
+use std::fmt::Write as _;
+use std::io;
+
+/// 一段加加速度恒定的运动，速度、加速度为段首的值
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    duration: f64,
+    velocity: f64,
+    acceleration: f64,
+    jerk: f64,
+}
+
+impl Segment {
+    fn distance(&self, t: f64) -> f64 {
+        self.velocity * t + self.acceleration * t * t / 2.0 + self.jerk * t * t * t / 6.0
+    }
+
+    fn velocity(&self, t: f64) -> f64 {
+        self.velocity + self.acceleration * t + self.jerk * t * t / 2.0
+    }
+
+    fn acceleration(&self, t: f64) -> f64 {
+        self.acceleration + self.jerk * t
+    }
+}
+
+/// 按顺序拼接各段，记录段末的速度
+struct Segments {
+    segments: Vec<Segment>,
+    velocity: f64,
+}
+
+impl Segments {
+    fn push(&mut self, duration: f64, acceleration: f64, jerk: f64) {
+        if duration <= 0.0 {
+            return;
+        }
+        let segment = Segment {
+            duration,
+            velocity: self.velocity,
+            acceleration,
+            jerk,
+        };
+        self.velocity = segment.velocity(duration);
+        self.segments.push(segment);
+    }
+
+    /// 速度从当前值变为 `target` 的一段加速或减速
+    fn ramp(&mut self, ramp: &Ramp, target: f64) {
+        let delta = (target - self.velocity).abs();
+        let sign = if target > self.velocity { 1.0 } else { -1.0 };
+        match ramp.jerk {
+            None => self.push(delta / ramp.acceleration, sign * ramp.acceleration, 0.0),
+            Some(jerk) if delta >= ramp.acceleration * ramp.acceleration / jerk => {
+                let t = ramp.acceleration / jerk;
+                self.push(t, 0.0, sign * jerk);
+                self.push(delta / ramp.acceleration - t, sign * ramp.acceleration, 0.0);
+                self.push(t, sign * ramp.acceleration, -sign * jerk);
+            }
+            Some(jerk) => {
+                let t = (delta / jerk).sqrt();
+                self.push(t, 0.0, sign * jerk);
+                self.push(t, sign * jerk * t, -sign * jerk);
+            }
+        }
+        self.velocity = target;
+    }
+
+    /// 以当前速度匀速运动 `distance`
+    fn cruise(&mut self, distance: f64) {
+        if self.velocity > 0.0 {
+            self.push(distance / self.velocity, 0.0, 0.0);
+        }
+    }
+
+    fn distance(&self) -> f64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.distance(segment.duration))
+            .sum()
+    }
+
+    /// 在走过 `distance` 处截断
+    fn truncate(&mut self, distance: f64) {
+        let mut remaining = distance;
+        for (index, segment) in self.segments.iter_mut().enumerate() {
+            let length = segment.distance(segment.duration);
+            if length < remaining {
+                remaining -= length;
+                continue;
+            }
+            // 段内速度为正，走过的距离随时间单调增加
+            let (mut low, mut high) = (0.0, segment.duration);
+            for _ in 0..64 {
+                let middle = (low + high) / 2.0;
+                if segment.distance(middle) < remaining {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            segment.duration = high;
+            self.velocity = segment.velocity(high);
+            self.segments.truncate(index + 1);
+            return;
+        }
+    }
+}
+
+/// 加速或减速段的参数
+struct Ramp {
+    acceleration: f64,
+    jerk: Option<f64>,
+}
+
+impl Ramp {
+    /// 速度在 `from` 与 `to` 之间变化所需的时间
+    fn time(&self, from: f64, to: f64) -> f64 {
+        let delta = (to - from).abs();
+        match self.jerk {
+            None => delta / self.acceleration,
+            Some(jerk) if delta >= self.acceleration * self.acceleration / jerk => {
+                delta / self.acceleration + self.acceleration / jerk
+            }
+            Some(jerk) => 2.0 * (delta / jerk).sqrt(),
+        }
+    }
+
+    /// 速度在 `from` 与 `to` 之间变化所需的距离。
+    ///
+    /// 直线与 S 曲线的加减速段都关于中点对称，平均速度即两端速度的平均值。
+    fn distance(&self, from: f64, to: f64) -> f64 {
+        (from + to) / 2.0 * self.time(from, to)
+    }
+}
+
+/// [`Profile::samples`] 一次最多取样的个数
+pub const MAX_SAMPLES: usize = 1_000_000;
+
+/// 芯片复位后加速计数器偏移 AO 的值
+pub const DEFAULT_ACCEL_OFFSET: i16 = 8;
+
+/// 手动减速时写入的减速点 DP 与加速计数器偏移 AO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManualDeceleration {
+    /// 开始减速时已输出的脉冲数
+    pub decel_point: u32,
+    /// 减速点提前的脉冲数
+    pub offset: i16,
+}
+
+impl ManualDeceleration {
+    /// 由速度曲线参数与输出脉冲数计算减速点与偏移。
+    ///
+    /// S 曲线或加、减速度不同时芯片无法自动计算减速点。这里按理想曲线求出减速所需的
+    /// 脉冲数，再按芯片的量化误差将减速点提前，使驱动在输出完全部脉冲之前降到初始速度。
+    ///
+    /// 芯片的速度以倍率 M 为单位阶梯变化，每一级都保持到理想曲线降到下一级为止，平均
+    /// 比理想曲线高半级；历时 T 的减速段因此多输出约 M × T / 2 个脉冲，向上取整即为偏移。
+    /// 多减掉的零头在降到初始速度后以初始速度输出。
+    pub fn new(drive: &DriveConfig, pulses: u32) -> Result<Self, Error> {
+        let profile = Profile::new(drive, pulses)?;
+        let (_, decel) = Profile::ramps(drive);
+        let time = decel.time(profile.peak_speed, profile.start_speed);
+        let offset = (drive.multiplier() * time / 2.0).ceil();
+        Ok(Self::before(
+            profile.decel_point,
+            pulses,
+            offset.min(i16::MAX as f64) as i16,
+        ))
+    }
+
+    /// 同 [`new`](Self::new)，减速点提前 `offset` 个脉冲
+    pub fn with_offset(drive: &DriveConfig, pulses: u32, offset: i16) -> Result<Self, Error> {
+        Ok(Self::before(
+            Profile::new(drive, pulses)?.decel_point,
+            pulses,
+            offset,
+        ))
+    }
+
+    /// 减速点比理想的 `decel_point` 提前 `offset` 个脉冲
+    fn before(decel_point: u32, pulses: u32, offset: i16) -> Self {
+        let decel_point = decel_point as i64 - offset as i64;
+        Self {
+            decel_point: decel_point.clamp(0, pulses as i64) as u32,
+            offset,
+        }
+    }
+}
+
+/// 某一时刻的运动状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// 自驱动开始的时间（s）
+    pub time: f64,
+    /// 已输出的脉冲数
+    pub position: f64,
+    /// 速度（pps）
+    pub velocity: f64,
+    /// 加速度（pps/s）
+    pub acceleration: f64,
+}
+
+/// 定长驱动的速度曲线预测，不需要连接设备。
+///
+/// 按理想的直线或 S 曲线加减速计算，忽略芯片内部的离散化；自动减速时，
+/// 减速段结束的同时输出完最后一个脉冲，速度降到初始速度后立即停止。
+///
+/// 速度曲线参数不合法（例如加速度为 0）时无法计算，构造时返回 [`Error::InvalidConfig`]。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    segments: Vec<Segment>,
+    /// 输出脉冲数 P
+    pub pulses: u32,
+    /// 减速点的确定方式
+    pub mode: DecelerationMode,
+    /// 初始速度（pps）
+    pub start_speed: f64,
+    /// 实际达到的最高速度（pps）
+    pub peak_speed: f64,
+    /// 未能加速到驱动速度即开始减速（三角形曲线）
+    pub triangle: bool,
+    /// 开始减速时已输出的脉冲数
+    pub decel_point: u32,
+    /// 驱动所需的时间（s）
+    pub duration: f64,
+}
+
+impl Profile {
+    /// 自动减速时的速度曲线
+    pub fn new(drive: &DriveConfig, pulses: u32) -> Result<Self, Error> {
+        check_problems("drive.", drive.problems())?;
+        let actual = drive.actual();
+        let (accel, decel) = Self::ramps(drive);
+        let (start, speed) = (actual.start_speed, actual.speed);
+        let total = pulses as f64;
+
+        // 加、减速距离随最高速度单调增加，距离不够时二分查找能达到的最高速度
+        let needed = |peak: f64| accel.distance(start, peak) + decel.distance(peak, start);
+        let peak = if needed(speed) <= total {
+            speed
+        } else {
+            let (mut low, mut high) = (start, speed);
+            for _ in 0..64 {
+                let middle = (low + high) / 2.0;
+                if needed(middle) <= total {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            low
+        };
+
+        let mut segments = Segments {
+            segments: Vec::new(),
+            velocity: start,
+        };
+        segments.ramp(&accel, peak);
+        let decel_distance = decel.distance(peak, start);
+        segments.cruise(total - segments.distance() - decel_distance);
+        segments.ramp(&decel, start);
+
+        Ok(Self::finish(
+            segments,
+            pulses,
+            DecelerationMode::Auto,
+            actual.start_speed,
+            actual.speed,
+            (total - decel_distance).max(0.0).floor() as u32,
+        ))
+    }
+
+    /// 手动减速时的速度曲线：输出 `decel_point` 个脉冲后开始减速。
+    ///
+    /// 减速点过早时减速到初始速度后以初始速度走完剩余的脉冲；减速点过晚时在输出完
+    /// 全部脉冲时停止，此时速度尚未降到初始速度。
+    pub fn manual(drive: &DriveConfig, pulses: u32, decel_point: u32) -> Result<Self, Error> {
+        check_problems("drive.", drive.problems())?;
+        let actual = drive.actual();
+        let (accel, decel) = Self::ramps(drive);
+        let start = actual.start_speed;
+        let total = pulses as f64;
+        let decel_at = decel_point.min(pulses) as f64;
+
+        let mut segments = Segments {
+            segments: Vec::new(),
+            velocity: start,
+        };
+        segments.ramp(&accel, actual.speed);
+        if segments.distance() > decel_at {
+            segments.truncate(decel_at);
+        } else {
+            segments.cruise(decel_at - segments.distance());
+        }
+        segments.ramp(&decel, start);
+        if segments.distance() > total {
+            segments.truncate(total);
+        } else {
+            segments.cruise(total - segments.distance());
+        }
+
+        Ok(Self::finish(
+            segments,
+            pulses,
+            DecelerationMode::Manual,
+            start,
+            actual.speed,
+            decel_at as u32,
+        ))
+    }
+
+    fn ramps(drive: &DriveConfig) -> (Ramp, Ramp) {
+        let actual = drive.actual();
+        let jerk = match drive.curve {
+            LineOrCurve::Line => None,
+            LineOrCurve::SCurve => actual.jerk,
+        };
+        let accel = Ramp {
+            acceleration: actual.acceleration,
+            jerk,
+        };
+        let decel = Ramp {
+            acceleration: actual.deceleration.unwrap_or(actual.acceleration),
+            jerk,
+        };
+        (accel, decel)
+    }
+
+    fn finish(
+        segments: Segments,
+        pulses: u32,
+        mode: DecelerationMode,
+        start_speed: f64,
+        speed: f64,
+        decel_point: u32,
+    ) -> Self {
+        let segments = segments.segments;
+        let peak_speed = segments
+            .iter()
+            .flat_map(|segment| [segment.velocity, segment.velocity(segment.duration)])
+            .fold(start_speed, f64::max);
+        Self {
+            duration: segments.iter().map(|segment| segment.duration).sum(),
+            triangle: peak_speed < speed * (1.0 - 1e-9),
+            segments,
+            pulses,
+            mode,
+            start_speed,
+            peak_speed,
+            decel_point,
+        }
+    }
+
+    /// `time` 时刻的运动状态，超出驱动时间时为停止后的状态
+    pub fn sample(&self, time: f64) -> Sample {
+        let mut elapsed = 0.0;
+        let mut position = 0.0;
+        for segment in &self.segments {
+            if time < elapsed + segment.duration {
+                let t = (time - elapsed).max(0.0);
+                return Sample {
+                    time,
+                    position: position + segment.distance(t),
+                    velocity: segment.velocity(t),
+                    acceleration: segment.acceleration(t),
+                };
+            }
+            elapsed += segment.duration;
+            position += segment.distance(segment.duration);
+        }
+        Sample {
+            time,
+            position,
+            velocity: 0.0,
+            acceleration: 0.0,
+        }
+    }
+
+    /// 从 0 开始每隔 `interval` 秒取样，最后一个样本为驱动结束的时刻。
+    ///
+    /// `interval` 不是正的有限值，或者样本数会超过 [`MAX_SAMPLES`] 时返回
+    /// [`Error::OutOfRange`]。
+    pub fn samples(&self, interval: f64) -> Result<Vec<Sample>, Error> {
+        let valid = interval > 0.0 && interval.is_finite();
+        if !(valid && self.duration / interval < MAX_SAMPLES as f64) {
+            return Err(Error::OutOfRange {
+                quantity: "sample interval",
+                value: interval,
+            });
+        }
+        let count = (self.duration / interval).ceil() as usize;
+        Ok((0..count)
+            .map(|i| self.sample(i as f64 * interval))
+            .chain([self.sample(self.duration)])
+            .collect())
+    }
+
+    /// 以 CSV 输出取样结果，表头为 `time,position,velocity,acceleration`
+    pub fn to_csv(&self, interval: f64) -> Result<String, Error> {
+        let mut csv = String::from("time,position,velocity,acceleration\n");
+        for sample in self.samples(interval)? {
+            let _ = writeln!(
+                csv,
+                "{:.6},{:.3},{:.3},{:.3}",
+                sample.time, sample.position, sample.velocity, sample.acceleration
+            );
+        }
+        Ok(csv)
+    }
+
+    /// 将 [`to_csv`](Self::to_csv) 的结果写入 `writer`，`interval` 不合法时返回
+    /// [`io::ErrorKind::InvalidInput`]
+    pub fn write_csv(&self, mut writer: impl io::Write, interval: f64) -> io::Result<()> {
+        let csv = self
+            .to_csv(interval)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        writer.write_all(csv.as_bytes())
+    }
+}
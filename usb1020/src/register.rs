@@ -1,3 +1,4 @@
+use crate::common::{Axis, Direction};
 use deku::prelude::*;
 
 
@@ -6,7 +7,8 @@ pub type MainStatusRegister = RR0;
 /// 在软件使用说明书中称为 `USB1020_PARA_RR0`。
 ///
 /// 主状态寄存器。
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 pub struct RR0 {
     /// X 轴的驱动状态
@@ -58,10 +60,33 @@ pub struct RR0 {
     pub bit_interpolate_stack_counter: u8,
 }
 
+impl RR0 {
+    /// 指定轴是否正在驱动
+    pub fn drv(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::X => self.x_drv,
+            Axis::Y => self.y_drv,
+            Axis::Z => self.z_drv,
+            Axis::U => self.u_drv,
+        }
+    }
+
+    /// 指定轴是否处于出错状态，具体原因见该轴的 RR2
+    pub fn error(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::X => self.x_error,
+            Axis::Y => self.y_error,
+            Axis::Z => self.z_error,
+            Axis::U => self.u_error,
+        }
+    }
+}
+
 
 pub type AxisStatusRegister = RR1;
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 /// 在软件使用说明书中称为 `USB1020_PARA_RR1`。
 ///
@@ -132,10 +157,23 @@ pub struct RR1 {
     pub emergency: bool,
 }
 
+impl RR1 {
+    /// 当前阶段改写驱动速度 V 能否生效：S 曲线加/减速中与减速中芯片忽略改写
+    pub fn accepts_speed_change(&self) -> bool {
+        !(self.v_descending || self.a_ascending || self.a_const || self.a_descending)
+    }
+
+    /// 当前阶段改写输出脉冲数 P 能否生效：减速中芯片忽略改写
+    pub fn accepts_pulse_change(&self) -> bool {
+        !self.v_descending
+    }
+}
+
 
 pub type AxisErrorRegister = RR2;
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 /// 在软件使用说明书中称为 `USB1020_PARA_RR2`
 pub struct RR2 {
@@ -173,8 +211,54 @@ pub struct RR2 {
     pub home_step: AutoHomeSearchStep,
 }
 
+impl RR2 {
+    /// 将出错状态转换为对应的错误，优先级依次为急停、伺服报警、限位、原点搜寻出错
+    pub fn check(&self, axis: Axis) -> Result<(), crate::Error> {
+        use crate::Error::*;
+
+        let limit = |direction, software| LimitHit {
+            axis,
+            direction,
+            software,
+        };
+        match self {
+            RR2 { emergency: true, .. } => Err(EmergencyStop { axis }),
+            RR2 {
+                servo_alarm: true, ..
+            } => Err(ServoAlarm { axis }),
+            RR2 {
+                hardware_limit_plus: true,
+                ..
+            } => Err(limit(Direction::Plus, false)),
+            RR2 {
+                hardware_limit_minus: true,
+                ..
+            } => Err(limit(Direction::Minus, false)),
+            RR2 {
+                software_limit_plus: true,
+                ..
+            } => Err(limit(Direction::Plus, true)),
+            RR2 {
+                software_limit_minus: true,
+                ..
+            } => Err(limit(Direction::Minus, true)),
+            RR2 {
+                home_error: true,
+                home_step,
+                ..
+            } => Err(HomingFailed {
+                axis,
+                step: *home_step,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// 自动原点搜寻中执行的步数
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[deku(
 type = "u8",
 bits = "5",
@@ -215,7 +299,8 @@ pub type ExternalSignalXY = RR3;
 pub type ExternalSignalZU = RR4;
 
 /// 在软件使用说明书中称为 `USB1020_PARA_RR3`
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 pub struct RR3 {
     pub x: AxisExternalSignal,
@@ -223,14 +308,16 @@ pub struct RR3 {
 }
 
 /// 在软件使用说明书中称为 `USB1020_PARA_RR4`
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 pub struct RR4 {
     pub z: AxisExternalSignal,
     pub u: AxisExternalSignal,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "endian", ctx = "endian: deku::ctx::Endian")]
 pub struct AxisExternalSignal {
     /// 外部停止信号 IN0 的电平状态
@@ -272,7 +359,8 @@ pub type InterruptRegister = RR5;
 /// 在软件使用说明书中称为 `USB1020_PARA_RR5`。
 ///
 /// 表明本次中断的原因。
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 pub struct RR5 {
     /// 产生一个增量脉冲
@@ -316,7 +404,8 @@ pub struct RR5 {
     pub sync: bool,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[deku(endian = "little")]
 pub struct PulseBitFiled {
     ///
@@ -368,7 +457,9 @@ pub struct PulseBitFiled {
     pub inpos: InPos,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[deku(
 type = "u8",
 bits = "2",
@@ -376,6 +467,7 @@ endian = "endian",
 ctx = "endian: deku::ctx::Endian")]
 pub enum Alarm {
     /// A/B相方式
+    #[default]
     #[deku(id = "0")]
     Disable,
     /// 上/下脉冲输入方式
@@ -386,7 +478,9 @@ pub enum Alarm {
     EnableWithLog,
 }
 
-#[derive(Debug, PartialEq, Eq, DekuRead, DekuWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DekuRead, DekuWrite, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[deku(
 type = "u8",
 bits = "2",
@@ -394,6 +488,7 @@ endian = "endian",
 ctx = "endian: deku::ctx::Endian")]
 pub enum InPos {
     /// A/B相方式
+    #[default]
     #[deku(id = "0")]
     Disable,
     /// 上/下脉冲输入方式
@@ -404,4 +499,14 @@ pub enum InPos {
     EnableWithLog,
 }
 
-pub enum PulseOutputMode {}
\ No newline at end of file
+/// 驱动脉冲的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PulseOutputMode {
+    /// 独立 2 脉冲方式：正向驱动脉冲从 +PLS 输出，反向从 -PLS 输出
+    #[default]
+    CwCcw,
+    /// 1 脉冲方式：驱动脉冲从 PLS 输出，DIR 输出方向
+    PulseDirection,
+}
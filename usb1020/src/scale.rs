@@ -0,0 +1,269 @@
+use crate::common::{Axis, Direction, OneOrAllAxis};
+use crate::config::{check_problems, SPEED};
+use crate::{During, Error, USB1020Controller};
+use rusb::{GlobalContext, UsbContext};
+
+/// 工程单位（mm、度等）与脉冲之间的换算
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct AxisScale {
+    /// 每个工程单位对应的电机脉冲数，不含电子齿轮比
+    pub steps_per_unit: f64,
+    /// 电子齿轮比的分子，输出脉冲数 = 电机脉冲数 × 分子 / 分母
+    pub gear_numerator: u32,
+    /// 电子齿轮比的分母
+    pub gear_denominator: u32,
+    /// 工程单位的正方向对应驱动的反方向，LP/EP 的读数也随之取反
+    pub invert: bool,
+    /// 每个工程单位对应的编码器计数，为 `None` 时与输出脉冲数相同
+    pub encoder_counts_per_unit: Option<f64>,
+}
+
+impl Default for AxisScale {
+    fn default() -> Self {
+        Self {
+            steps_per_unit: 1.0,
+            gear_numerator: 1,
+            gear_denominator: 1,
+            invert: false,
+            encoder_counts_per_unit: None,
+        }
+    }
+}
+
+impl AxisScale {
+    /// 每个工程单位对应的输出脉冲数（LP 计数）
+    pub fn pulses_per_unit(&self) -> f64 {
+        self.steps_per_unit * self.gear_numerator as f64 / self.gear_denominator as f64
+    }
+
+    /// 每个工程单位对应的编码器计数（EP 计数）
+    pub fn counts_per_unit(&self) -> f64 {
+        self.encoder_counts_per_unit
+            .unwrap_or_else(|| self.pulses_per_unit())
+    }
+
+    fn sign(&self) -> f64 {
+        if self.invert {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    /// 检查换算参数，返回出错的项与原因
+    pub(crate) fn problems(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        if !(self.steps_per_unit.is_finite() && self.steps_per_unit > 0.0) {
+            problems.push((
+                "steps_per_unit",
+                format!("must be positive, got {}", self.steps_per_unit),
+            ));
+        }
+        if self.gear_numerator == 0 {
+            problems.push(("gear_numerator", "must not be 0".to_owned()));
+        }
+        if self.gear_denominator == 0 {
+            problems.push(("gear_denominator", "must not be 0".to_owned()));
+        }
+        if let Some(counts) = self.encoder_counts_per_unit {
+            if !(counts.is_finite() && counts > 0.0) {
+                problems.push((
+                    "encoder_counts_per_unit",
+                    format!("must be positive, got {}", counts),
+                ));
+            }
+        }
+        problems
+    }
+
+    fn to_counter(quantity: &'static str, value: f64, per_unit: f64) -> Result<i32, Error> {
+        let counts = (value * per_unit).round();
+        if counts >= i32::MIN as f64 && counts <= i32::MAX as f64 {
+            Ok(counts as i32)
+        } else {
+            Err(Error::OutOfRange { quantity, value })
+        }
+    }
+
+    /// 工程单位的位置或距离换算为输出脉冲数（带符号）
+    pub fn to_pulses(&self, units: f64) -> Result<i32, Error> {
+        Self::to_counter("position", units, self.pulses_per_unit() * self.sign())
+    }
+
+    /// 输出脉冲数（LP）换算为工程单位
+    pub fn from_pulses(&self, pulses: i32) -> f64 {
+        pulses as f64 / self.pulses_per_unit() * self.sign()
+    }
+
+    /// 工程单位的位置换算为编码器计数
+    pub fn to_counts(&self, units: f64) -> Result<i32, Error> {
+        Self::to_counter(
+            "encoder position",
+            units,
+            self.counts_per_unit() * self.sign(),
+        )
+    }
+
+    /// 编码器计数（EP）换算为工程单位
+    pub fn from_counts(&self, counts: i32) -> f64 {
+        counts as f64 / self.counts_per_unit() * self.sign()
+    }
+
+    /// 工程单位的方向换算为驱动方向
+    pub fn direction(&self, direction: Direction) -> Direction {
+        match (self.invert, direction) {
+            (false, direction) => direction,
+            (true, Direction::Plus) => Direction::Minus,
+            (true, Direction::Minus) => Direction::Plus,
+        }
+    }
+
+    fn to_register(quantity: &'static str, value: f64, register: f64) -> Result<u16, Error> {
+        let register = register.round();
+        if register >= *SPEED.start() as f64 && register <= *SPEED.end() as f64 {
+            Ok(register as u16)
+        } else {
+            Err(Error::OutOfRange { quantity, value })
+        }
+    }
+
+    /// 速度（单位/s）换算为倍率 M 下的速度寄存器值（SV、V）
+    pub fn speed_register(&self, speed: f64, multiplier: f64) -> Result<u16, Error> {
+        let pps = speed.abs() * self.pulses_per_unit();
+        Self::to_register("speed", speed, pps / multiplier)
+    }
+
+    /// 加速度（单位/s²）换算为倍率 M 下的加/减速度寄存器值（A、D）
+    pub fn acceleration_register(&self, acceleration: f64, multiplier: f64) -> Result<u16, Error> {
+        let pps2 = acceleration.abs() * self.pulses_per_unit();
+        Self::to_register("acceleration", acceleration, pps2 / (125.0 * multiplier))
+    }
+}
+
+/// 以工程单位操作的轴，由 [`USB1020Controller::scaled`] 得到。
+///
+/// 速度与加速度按轴当前的倍率换算为寄存器值，超出寄存器范围时返回
+/// [`Error::OutOfRange`]，此时需要换用合适的倍率。
+#[derive(Debug)]
+pub struct ScaledAxis<'a, T: UsbContext = GlobalContext> {
+    controller: &'a USB1020Controller<T>,
+    axis: Axis,
+    scale: AxisScale,
+}
+
+impl<'a, T: UsbContext> ScaledAxis<'a, T> {
+    pub fn axis(&self) -> Axis {
+        self.axis
+    }
+
+    pub fn scale(&self) -> &AxisScale {
+        &self.scale
+    }
+
+    fn one(&self) -> OneOrAllAxis {
+        OneOrAllAxis::One(self.axis)
+    }
+
+    /// 由逻辑位置计数器 LP 得到的位置
+    pub fn position(&self) -> Result<f64, Error> {
+        let pulses = self.controller.read_lp(self.axis)? as i32;
+        Ok(self.scale.from_pulses(pulses))
+    }
+
+    /// 由实位计数器 EP 得到的位置
+    pub fn encoder_position(&self) -> Result<f64, Error> {
+        let counts = self.controller.read_ep(self.axis)? as i32;
+        Ok(self.scale.from_counts(counts))
+    }
+
+    /// 将当前位置设为 `position`，同时改写 LP 与 EP
+    pub fn set_position(&self, position: f64) -> Result<(), Error> {
+        let pulses = self.scale.to_pulses(position)?;
+        let counts = self.scale.to_counts(position)?;
+        self.controller.set_lp(self.one(), pulses as u32)?;
+        self.controller.set_ep(self.one(), counts as u32)
+    }
+
+    /// 初始速度（单位/s）
+    pub fn set_start_speed(&self, speed: f64) -> Result<(), Error> {
+        let register = self
+            .scale
+            .speed_register(speed, self.controller.multiplier(self.axis))?;
+        self.controller.set_start_speed(self.one(), register)
+    }
+
+    /// 驱动速度（单位/s）
+    pub fn set_speed(&self, speed: f64) -> Result<(), Error> {
+        let register = self
+            .scale
+            .speed_register(speed, self.controller.multiplier(self.axis))?;
+        self.controller.set_speed(self.one(), register)
+    }
+
+    /// 加速度（单位/s²）
+    pub fn set_acceleration(&self, acceleration: f64) -> Result<(), Error> {
+        let register = self
+            .scale
+            .acceleration_register(acceleration, self.controller.multiplier(self.axis))?;
+        self.controller.set_acceleration(self.one(), register)
+    }
+
+    /// 减速度（单位/s²），仅在加、减速分别设定时有效
+    pub fn set_deceleration(&self, deceleration: f64) -> Result<(), Error> {
+        let register = self
+            .scale
+            .acceleration_register(deceleration, self.controller.multiplier(self.axis))?;
+        self.controller.set_deceleration(self.one(), register)
+    }
+
+    fn move_pulses(&self, pulses: i64) -> Result<(), Error> {
+        let direction = if pulses < 0 {
+            Direction::Minus
+        } else {
+            Direction::Plus
+        };
+        let pulses = u32::try_from(pulses.unsigned_abs()).map_err(|_| Error::OutOfRange {
+            quantity: "distance",
+            value: pulses as f64,
+        })?;
+        if pulses == 0 {
+            return Ok(());
+        }
+        self.controller.set_pulse(self.one(), pulses)?;
+        self.controller.fixed_drive(self.one(), direction)
+    }
+
+    /// 相对当前位置移动 `distance`，不等待驱动结束
+    pub fn move_by(&self, distance: f64) -> Result<(), Error> {
+        self.move_pulses(self.scale.to_pulses(distance)?.into())
+    }
+
+    /// 移动到 `position`，不等待驱动结束。
+    ///
+    /// 按脉冲数计算与当前 LP 的差值，多次移动不会累积换算的舍入误差。
+    pub fn move_to(&self, position: f64) -> Result<(), Error> {
+        let target = self.scale.to_pulses(position)?;
+        let current = self.controller.read_lp(self.axis)? as i32;
+        self.move_pulses(i64::from(target) - i64::from(current))
+    }
+
+    /// 向工程单位的 `direction` 方向连续驱动
+    pub fn jog(&self, direction: Direction) -> Result<(), Error> {
+        self.controller
+            .continuous_drive(self.one(), self.scale.direction(direction))
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 以工程单位操作 `axis`，换算参数不合法时返回 [`Error::InvalidConfig`]
+    pub fn scaled(&self, axis: Axis, scale: AxisScale) -> Result<ScaledAxis<'_, T>, Error> {
+        check_problems("scale.", scale.problems()).during("scaled", Some(axis))?;
+        Ok(ScaledAxis {
+            controller: self,
+            axis,
+            scale,
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use crate::common::OneOrAllAxis;
+use crate::{Error, USB1020Controller};
+use rusb::{GlobalContext, UsbContext};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+struct Inner<T: UsbContext> {
+    controller: Mutex<USB1020Controller<T>>,
+    turn: Mutex<Turn>,
+    turn_released: Condvar,
+}
+
+/// 由谁使用设备。不直接依赖 `controller` 的锁排队，因为 [`Mutex`] 不保证唤醒顺序
+#[derive(Default)]
+struct Turn {
+    /// 有操作正在使用设备
+    busy: bool,
+    /// 正在等待执行的停止命令数，大于 0 时新的普通操作需要等待
+    pending_stops: usize,
+}
+
+/// 可在线程间共享的控制器句柄。
+///
+/// 像 [`get_rr1`](USB1020Controller::get_rr1) 这样先选择轴再读寄存器的操作由多次传输组成，
+/// 多个线程直接共用一个控制器时可能相互穿插，读到其他轴的寄存器。
+/// [`with`](Self::with) 在整个闭包执行期间独占设备，保证其中的多步操作不被打断；
+/// 停止命令则会插到所有尚未开始的普通操作之前执行。
+///
+/// 正在执行的闭包不会被打断，停止命令要等它返回后才发出，因此传给 `with` 的闭包
+/// 应尽量短，不要在其中等待驱动结束。
+pub struct SharedController<T: UsbContext = GlobalContext> {
+    inner: Arc<Inner<T>>,
+}
+
+/// 离开作用域时释放设备，闭包 panic 时也不会让其他线程一直等待
+struct Release<'a, T: UsbContext> {
+    shared: &'a SharedController<T>,
+    /// 释放的是停止命令，需同时减少等待中的停止命令数
+    stop: bool,
+}
+
+impl<T: UsbContext> Drop for Release<'_, T> {
+    fn drop(&mut self) {
+        let mut turn = self.shared.turn();
+        turn.busy = false;
+        if self.stop {
+            turn.pending_stops -= 1;
+        }
+        drop(turn);
+        self.shared.inner.turn_released.notify_all();
+    }
+}
+
+impl<T: UsbContext> Clone for SharedController<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: UsbContext> From<USB1020Controller<T>> for SharedController<T> {
+    fn from(controller: USB1020Controller<T>) -> Self {
+        Self::new(controller)
+    }
+}
+
+impl<T: UsbContext> SharedController<T> {
+    pub fn new(controller: USB1020Controller<T>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                controller: Mutex::new(controller),
+                turn: Mutex::new(Turn::default()),
+                turn_released: Condvar::new(),
+            }),
+        }
+    }
+
+    fn controller(&self) -> MutexGuard<'_, USB1020Controller<T>> {
+        self.inner
+            .controller
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn turn(&self) -> MutexGuard<'_, Turn> {
+        self.inner
+            .turn
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 等待 `blocked` 不再成立后占用设备
+    fn acquire(&self, mut turn: MutexGuard<'_, Turn>, blocked: fn(&Turn) -> bool) {
+        while blocked(&turn) {
+            turn = self
+                .inner
+                .turn_released
+                .wait(turn)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        turn.busy = true;
+    }
+
+    /// 独占设备执行一组操作，期间其他线程的操作不会穿插进来
+    pub fn with<R>(&self, f: impl FnOnce(&USB1020Controller<T>) -> R) -> R {
+        self.acquire(self.turn(), |turn| turn.busy || turn.pending_stops > 0);
+        let _release = Release {
+            shared: self,
+            stop: false,
+        };
+        f(&self.controller())
+    }
+
+    /// 优先执行停止命令：等待当前正在执行的操作结束后立即执行，登记之后才开始等待的
+    /// 普通操作都排在它之后
+    fn stop(
+        &self,
+        f: impl FnOnce(&USB1020Controller<T>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut turn = self.turn();
+        turn.pending_stops += 1;
+        self.acquire(turn, |turn| turn.busy);
+        let _release = Release {
+            shared: self,
+            stop: true,
+        };
+        f(&self.controller())
+    }
+
+    /// 优先发送的减速停止
+    pub fn decel_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.stop(|controller| controller.decel_stop(axis))
+    }
+
+    /// 优先发送的立即停止
+    pub fn sudden_stop(&self, axis: OneOrAllAxis) -> Result<(), Error> {
+        self.stop(|controller| controller.sudden_stop(axis))
+    }
+}
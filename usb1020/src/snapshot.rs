@@ -0,0 +1,270 @@
+use crate::common::Axis;
+use crate::register::*;
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 单个轴的状态
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisSnapshot {
+    /// 状态寄存器 RR1
+    pub status: AxisStatusRegister,
+    /// 出错寄存器 RR2
+    pub error: AxisErrorRegister,
+    /// 外部输入信号（RR3/RR4 中属于该轴的部分）
+    pub signals: AxisExternalSignal,
+    /// 逻辑位置计数器 LP
+    pub logic_position: i32,
+    /// 实位计数器 EP
+    pub real_position: i32,
+    /// 当前驱动速度 CV
+    pub current_speed: u16,
+    /// 当前加/减速度 CA
+    pub current_acceleration: u16,
+}
+
+/// 整块卡在某一时刻的状态，由 [`USB1020Controller::snapshot`] 读取
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControllerSnapshot {
+    /// 开始读取的时刻
+    pub timestamp: SystemTime,
+    /// 主状态寄存器 RR0
+    pub main: MainStatusRegister,
+    /// 按 X、Y、Z、U 顺序排列的各轴状态
+    pub axes: [AxisSnapshot; 4],
+}
+
+impl ControllerSnapshot {
+    pub fn axis(&self, axis: Axis) -> &AxisSnapshot {
+        &self.axes[axis.index()]
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 在一个事务中读取整块卡的状态：RR0、RR3、RR4，以及各轴的 RR1、RR2、LP、EP、CV、CA
+    pub fn snapshot(&self) -> Result<ControllerSnapshot, Error> {
+        let timestamp = SystemTime::now();
+        let mut transaction = self.transaction();
+        let rr0 = transaction.read_rr(0);
+        let rr3 = transaction.read_rr(4);
+        let rr4 = transaction.read_rr(5);
+        let tickets = Axis::ALL.map(|axis| {
+            let bit = u16::from(axis.bit()) << 8;
+            transaction.select(axis);
+            let rr1 = transaction.read_rr(1);
+            let rr2 = transaction.read_rr(2);
+            transaction.write_pipe(0, bit | 0x10);
+            let lp = transaction.read_u32_data();
+            transaction.write_pipe(0, bit | 0x11);
+            let ep = transaction.read_u32_data();
+            transaction.write_pipe(0, bit | 0x12);
+            let cv = transaction.read_rr(6);
+            transaction.write_pipe(0, bit | 0x13);
+            let ca = transaction.read_rr(6);
+            (rr1, rr2, lp, ep, cv, ca)
+        });
+        let responses = transaction.flush()?;
+
+        let decode = || {
+            let rr3: RR3 = responses.decode(rr3)?;
+            let rr4: RR4 = responses.decode(rr4)?;
+            let mut signals = [rr3.x, rr3.y, rr4.z, rr4.u].into_iter();
+            let mut axes = Vec::with_capacity(4);
+            for (axis, (rr1, rr2, lp, ep, cv, ca)) in Axis::ALL.into_iter().zip(tickets) {
+                let (lp, ep) = (responses.get_u32(lp), responses.get_u32(ep));
+                self.remember_position(axis, Some(lp), Some(ep));
+                axes.push(AxisSnapshot {
+                    status: responses.decode(rr1)?,
+                    error: responses.decode(rr2)?,
+                    signals: signals.next().expect("four axes"),
+                    logic_position: lp as i32,
+                    real_position: ep as i32,
+                    current_speed: u16::from_le_bytes(responses.get(cv)),
+                    current_acceleration: u16::from_le_bytes(responses.get(ca)),
+                });
+            }
+            Ok(ControllerSnapshot {
+                timestamp,
+                main: responses.decode(rr0)?,
+                axes: axes.try_into().expect("four axes"),
+            })
+        };
+        decode().during("snapshot", None)
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "-"
+    }
+}
+
+/// 列出为真的标志，全部为假时返回 `-`
+fn flags(flags: &[(bool, &str)]) -> String {
+    let set: Vec<&str> = flags
+        .iter()
+        .filter(|(value, _)| *value)
+        .map(|(_, name)| *name)
+        .collect();
+    if set.is_empty() {
+        "-".to_owned()
+    } else {
+        set.join(",")
+    }
+}
+
+fn phase(status: &RR1) -> &'static str {
+    match status {
+        RR1 {
+            v_ascending: true, ..
+        } => "accel",
+        RR1 { v_const: true, .. } => "const",
+        RR1 {
+            v_descending: true, ..
+        } => "decel",
+        _ => "-",
+    }
+}
+
+fn stopped_by(status: &RR1) -> String {
+    flags(&[
+        (status.in0, "IN0"),
+        (status.in1, "IN1"),
+        (status.in2, "IN2"),
+        (status.in3, "IN3"),
+        (status.limit_plus, "LMT+"),
+        (status.limit_minus, "LMT-"),
+        (status.servo_alarm, "ALM"),
+        (status.emergency, "EMG"),
+    ])
+}
+
+fn errors(error: &RR2) -> String {
+    flags(&[
+        (error.software_limit_plus, "SLMT+"),
+        (error.software_limit_minus, "SLMT-"),
+        (error.hardware_limit_plus, "HLMT+"),
+        (error.hardware_limit_minus, "HLMT-"),
+        (error.servo_alarm, "ALM"),
+        (error.emergency, "EMG"),
+        (error.home_error, "HOME"),
+    ])
+}
+
+fn signals(signals: &AxisExternalSignal) -> String {
+    flags(&[
+        (signals.in0, "IN0"),
+        (signals.in1, "IN1"),
+        (signals.in2, "IN2"),
+        (signals.in3, "IN3"),
+        (signals.p_p, "EXPP"),
+        (signals.p_m, "EXPM"),
+        (signals.in_position, "INPOS"),
+        (signals.servo_alarm, "ALM"),
+    ])
+}
+
+impl fmt::Display for ControllerSnapshot {
+    /// 以表格形式输出，每行末尾附上说明书中的中文名称
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writeln!(
+            f,
+            "USB1020 snapshot at {}.{:03} (unix time)",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis()
+        )?;
+        writeln!(
+            f,
+            "interpolating: {}, next data: {}, zone: {}, stack counter: {}  # 主状态寄存器 RR0",
+            yes_no(self.main.i_drv),
+            yes_no(self.main.c_next),
+            self.main.zone,
+            self.main.bit_interpolate_stack_counter
+        )?;
+
+        let mut row = |label: &str, values: [String; 4], name: &str| {
+            writeln!(
+                f,
+                "{:<16}{:>14}{:>14}{:>14}{:>14}  # {}",
+                label, values[0], values[1], values[2], values[3], name
+            )
+        };
+        row("axis", ["X", "Y", "Z", "U"].map(str::to_owned), "轴")?;
+        row(
+            "driving",
+            Axis::ALL.map(|axis| yes_no(self.main.drv(axis)).to_owned()),
+            "驱动状态 (RR0)",
+        )?;
+        row(
+            "error",
+            Axis::ALL.map(|axis| yes_no(self.main.error(axis)).to_owned()),
+            "出错状态 (RR0)",
+        )?;
+        row(
+            "logic position",
+            self.axes
+                .each_ref()
+                .map(|axis| axis.logic_position.to_string()),
+            "逻辑位置计数器 LP",
+        )?;
+        row(
+            "real position",
+            self.axes
+                .each_ref()
+                .map(|axis| axis.real_position.to_string()),
+            "实位计数器 EP",
+        )?;
+        row(
+            "speed",
+            self.axes
+                .each_ref()
+                .map(|axis| axis.current_speed.to_string()),
+            "当前驱动速度 CV",
+        )?;
+        row(
+            "acceleration",
+            self.axes
+                .each_ref()
+                .map(|axis| axis.current_acceleration.to_string()),
+            "当前加/减速度 CA",
+        )?;
+        row(
+            "phase",
+            self.axes
+                .each_ref()
+                .map(|axis| phase(&axis.status).to_owned()),
+            "加/减速状态 (RR1)",
+        )?;
+        row(
+            "stopped by",
+            self.axes.each_ref().map(|axis| stopped_by(&axis.status)),
+            "驱动停止原因 (RR1)",
+        )?;
+        row(
+            "errors",
+            self.axes.each_ref().map(|axis| errors(&axis.error)),
+            "出错原因 (RR2)",
+        )?;
+        row(
+            "home step",
+            self.axes
+                .each_ref()
+                .map(|axis| format!("{:?}", axis.error.home_step)),
+            "自动原点搜寻步数 (RR2)",
+        )?;
+        row(
+            "inputs",
+            self.axes.each_ref().map(|axis| signals(&axis.signals)),
+            "外部输入信号电平 (RR3/RR4)",
+        )
+    }
+}
@@ -0,0 +1,273 @@
+use crate::common::{Axis, OneOrAllAxis};
+use crate::config::MachineConfig;
+use crate::device::device_path;
+use crate::{Error, KnownPosition, USB1020Controller};
+use rusb::{Context, Device, Hotplug, HotplugBuilder, Registration, UsbContext};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 连接状态的变化，通过 [`SupervisedController::subscribe`] 订阅
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ConnectionEvent {
+    /// 设备被拔出或掉线
+    Disconnected,
+    /// 设备重新插入，已完成初始化并恢复了配置与 LP/EP
+    Reconnected,
+    /// 设备重新插入，但重新打开或恢复状态失败
+    ReconnectFailed(String),
+}
+
+/// 热插拔回调发往重连线程的通知
+enum Notice {
+    Arrived,
+    Left,
+}
+
+/// 热插拔回调中不能进行同步传输，只转发与目标端口路径匹配的事件
+struct HotplugHandler {
+    path: String,
+    notices: Sender<Notice>,
+}
+
+impl HotplugHandler {
+    fn forward(&self, device: &Device<Context>, notice: Notice) {
+        if device_path(device).as_deref() == Some(self.path.as_str()) {
+            let _ = self.notices.send(notice);
+        }
+    }
+}
+
+impl Hotplug<Context> for HotplugHandler {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        self.forward(&device, Notice::Arrived);
+    }
+
+    fn device_left(&mut self, device: Device<Context>) {
+        self.forward(&device, Notice::Left);
+    }
+}
+
+#[derive(Default)]
+struct State {
+    controller: Option<USB1020Controller<Context>>,
+    /// 各轴最近一次读到或写入的 (LP, EP)
+    positions: [KnownPosition; 4],
+    /// 最近一次 [`SupervisedController::apply_config`] 写入的配置
+    config: Option<MachineConfig>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    subscribers: Mutex<Vec<Sender<ConnectionEvent>>>,
+}
+
+impl State {
+    /// 合并控制器最近一次读到或写入的 LP/EP
+    fn remember_positions(&mut self) {
+        if let Some(controller) = &self.controller {
+            for (known, latest) in self.positions.iter_mut().zip(controller.known_positions()) {
+                known.0 = latest.0.or(known.0);
+                known.1 = latest.1.or(known.1);
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify(&self, event: ConnectionEvent) {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    fn reconnect(&self, context: &Context, path: &str) -> Result<(), Error> {
+        let mut state = self.state();
+        state.remember_positions();
+        state.controller = None;
+
+        // 打开时已执行过 init_device
+        let controller = USB1020Controller::open_by_path_with(context, path)?;
+        if let Some(config) = &state.config {
+            controller.apply_config(config)?;
+        }
+        for axis in Axis::ALL {
+            let (lp, ep) = state.positions[axis.index()];
+            if let Some(lp) = lp {
+                controller.set_lp(OneOrAllAxis::One(axis), lp)?;
+            }
+            if let Some(ep) = ep {
+                controller.set_ep(OneOrAllAxis::One(axis), ep)?;
+            }
+        }
+        state.controller = Some(controller);
+
+        Ok(())
+    }
+}
+
+/// 带断线重连的控制器。
+///
+/// 通过 libusb 热插拔事件监视插在指定端口路径上的 USB1020。设备断开后，
+/// 所有操作返回 [`Error::Disconnected`]；设备重新插入后自动重新打开并初始化，
+/// 重新写入最近一次 [`apply_config`](Self::apply_config) 的配置，
+/// 恢复断开前最近一次读到或写入的 LP/EP，再通知订阅者。
+///
+/// 经 [`with`](Self::with) 执行的 `read_lp`、`read_ep`、`set_lp`、`set_ep` 与
+/// `snapshot` 都会记录位置；驱动中断开时，最后一次读取之后走过的脉冲无法恢复。
+pub struct SupervisedController {
+    context: Context,
+    shared: Arc<Shared>,
+    running: Arc<AtomicBool>,
+    registration: Option<Registration<Context>>,
+    event_thread: Option<JoinHandle<()>>,
+    reconnect_thread: Option<JoinHandle<()>>,
+}
+
+impl SupervisedController {
+    /// 设备插入后等待其完成枚举的时间
+    const SETTLE_TIME: Duration = Duration::from_millis(200);
+    /// 事件线程单次处理事件的超时，决定了停止监视时的最长等待
+    const EVENT_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// 打开插在形如 `1-3.2` 的端口路径上的 USB1020 并开始监视
+    pub fn open(context: Context, path: &str) -> Result<Self, Error> {
+        if !rusb::has_hotplug() {
+            return Err(Error::USBError(rusb::Error::NotSupported));
+        }
+
+        let controller = USB1020Controller::open_by_path_with(&context, path)?;
+        let shared = Arc::new(Shared::default());
+        shared.state().controller = Some(controller);
+
+        let (notices, receiver) = mpsc::channel();
+        let handler = HotplugHandler {
+            path: path.to_owned(),
+            notices,
+        };
+        let mut builder = HotplugBuilder::new();
+        builder
+            .vendor_id(USB1020Controller::<Context>::VENDOR_ID)
+            .product_id(USB1020Controller::<Context>::PRODUCT_ID);
+        let registration = builder.register::<Context, _>(context.clone(), Box::new(handler))?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let event_thread = {
+            let context = context.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::Acquire) {
+                    let _ = context.handle_events(Some(Self::EVENT_TIMEOUT));
+                }
+            })
+        };
+        let reconnect_thread = {
+            let context = context.clone();
+            let shared = shared.clone();
+            let path = path.to_owned();
+            thread::spawn(move || Self::reconnect_loop(&context, &shared, &path, receiver))
+        };
+
+        Ok(Self {
+            context,
+            shared,
+            running,
+            registration: Some(registration),
+            event_thread: Some(event_thread),
+            reconnect_thread: Some(reconnect_thread),
+        })
+    }
+
+    fn reconnect_loop(context: &Context, shared: &Shared, path: &str, notices: Receiver<Notice>) {
+        for notice in notices {
+            match notice {
+                Notice::Left => {
+                    let mut state = shared.state();
+                    state.remember_positions();
+                    state.controller = None;
+                    drop(state);
+                    shared.notify(ConnectionEvent::Disconnected);
+                }
+                Notice::Arrived => {
+                    thread::sleep(Self::SETTLE_TIME);
+                    shared.notify(match shared.reconnect(context, path) {
+                        Ok(()) => ConnectionEvent::Reconnected,
+                        Err(e) => ConnectionEvent::ReconnectFailed(e.to_string()),
+                    });
+                }
+            }
+        }
+    }
+
+    /// 订阅连接状态的变化
+    pub fn subscribe(&self) -> Receiver<ConnectionEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(sender);
+        receiver
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.shared.state().controller.is_some()
+    }
+
+    /// 在持有设备的情况下执行操作，设备断开时返回 [`Error::Disconnected`]
+    pub fn with<R>(
+        &self,
+        f: impl FnOnce(&USB1020Controller<Context>) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut state = self.shared.state();
+        let controller = state.controller.as_ref().ok_or(Error::Disconnected)?;
+        let result = f(controller);
+        state.remember_positions();
+        result
+    }
+
+    /// 写入配置并记录下来，重连后重新写入
+    pub fn apply_config(&self, config: MachineConfig) -> Result<(), Error> {
+        let mut state = self.shared.state();
+        let controller = state.controller.as_ref().ok_or(Error::Disconnected)?;
+        controller.apply_config(&config)?;
+        state.config = Some(config);
+        Ok(())
+    }
+
+    /// 读取并记录各轴当前的 LP/EP，重连后以此恢复
+    pub fn checkpoint(&self) -> Result<(), Error> {
+        self.with(|controller| {
+            for axis in Axis::ALL {
+                controller.read_lp(axis)?;
+                controller.read_ep(axis)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Drop for SupervisedController {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        // 注销回调会释放其中的 Sender，重连线程随之退出
+        drop(self.registration.take());
+        self.context.interrupt_handle_events();
+        for thread in [self.event_thread.take(), self.reconnect_thread.take()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = thread.join();
+        }
+    }
+}
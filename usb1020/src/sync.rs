@@ -0,0 +1,288 @@
+use crate::common::{Axes, Axis};
+use crate::{During, Error, USB1020Controller};
+use rusb::UsbContext;
+
+/// 同步动作设定（命令码 0x64）：数据 D0～D8 为触发条件，D12～D15 为触发时启动动作的轴，
+/// 高 16 位为该轴被启动时执行的动作
+pub(crate) const SYNC_SET: u8 = 0x64;
+/// 数据中动作轴掩码的位置
+const ACTION_AXES_SHIFT: u32 = 12;
+/// 同步动作启动（命令码 0x65），触发以 [`Provocation::Command`] 为条件的动作
+const SYNC_ACTIVATE: u8 = 0x65;
+
+/// 同步动作的触发条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Provocation {
+    /// 逻辑/实位计数器由小于变为大于等于 COMP+
+    CompPlusReached,
+    /// 逻辑/实位计数器由大于等于变为小于 COMP+
+    CompPlusLeft,
+    /// 逻辑/实位计数器由大于等于变为小于 COMP-
+    CompMinusReached,
+    /// 逻辑/实位计数器由小于变为大于等于 COMP-
+    CompMinusLeft,
+    /// 驱动开始
+    DriveStart,
+    /// 驱动结束
+    DriveEnd,
+    /// IN3 由无效变为有效
+    In3Rising,
+    /// IN3 由有效变为无效
+    In3Falling,
+    /// 同步动作启动命令
+    Command,
+}
+
+impl Provocation {
+    const ALL: [Provocation; 9] = [
+        Provocation::CompPlusReached,
+        Provocation::CompPlusLeft,
+        Provocation::CompMinusReached,
+        Provocation::CompMinusLeft,
+        Provocation::DriveStart,
+        Provocation::DriveEnd,
+        Provocation::In3Rising,
+        Provocation::In3Falling,
+        Provocation::Command,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// 同步动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Action {
+    /// 正方向定长驱动
+    FixedDrivePlus,
+    /// 反方向定长驱动
+    FixedDriveMinus,
+    /// 正方向连续驱动
+    ContinuousDrivePlus,
+    /// 反方向连续驱动
+    ContinuousDriveMinus,
+    /// 减速停止
+    DecelStop,
+    /// 立即停止
+    SuddenStop,
+    /// 将逻辑位置计数器 LP 锁存到 BR
+    LatchLogicPosition,
+    /// 将实位计数器 EP 锁存到 BR
+    LatchRealPosition,
+    /// 产生中断，见 RR5 的 `sync`
+    Interrupt,
+}
+
+impl Action {
+    const ALL: [Action; 9] = [
+        Action::FixedDrivePlus,
+        Action::FixedDriveMinus,
+        Action::ContinuousDrivePlus,
+        Action::ContinuousDriveMinus,
+        Action::DecelStop,
+        Action::SuddenStop,
+        Action::LatchLogicPosition,
+        Action::LatchRealPosition,
+        Action::Interrupt,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << (16 + self as u32)
+    }
+
+    /// 启动或停止驱动的动作，同一轴至多一个
+    fn is_drive(self) -> bool {
+        self as u32 <= Action::SuddenStop as u32
+    }
+
+    /// 锁存到 BR 的动作，同一轴至多一个
+    fn is_latch(self) -> bool {
+        matches!(self, Action::LatchLogicPosition | Action::LatchRealPosition)
+    }
+}
+
+/// 同步动作的设定，由 [`USB1020Controller::set_sync_actions`] 写入。
+///
+/// 触发条件在触发轴上，动作在动作轴上：触发轴的任一触发条件成立时，启动它的全部动作轴，
+/// 每个被启动的轴执行其全部动作。例如
+/// `.on(Axis::X, Provocation::In3Rising, Axis::X, Action::LatchRealPosition)` 在 IN3 有效时
+/// 锁存 X 轴的实位计数器，之后可由 [`read_latched`](USB1020Controller::read_latched) 读出；
+/// `.on(Axis::X, Provocation::CompPlusReached, Axis::Y, Action::FixedDrivePlus)` 在 X 轴
+/// 越过 COMP+ 时启动 Y 轴的定长驱动。
+///
+/// 芯片中每个轴只有一组触发条件与动作，同一触发轴的各条设定会相互牵连：例如
+/// `.on(X, CompPlusReached, Y, FixedDrivePlus).on(X, DriveEnd, Z, DecelStop)` 写入后，
+/// COMP+ 也会使 Z 停止。写入前检查出这种情况时返回 [`Error::UnintendedSyncAction`]。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncActions {
+    words: [u32; 4],
+    axes: Axes,
+    /// 按 [触发轴][动作轴] 记录要求的 (触发条件, 动作)，见 [`rule_bit`]
+    rules: [[u128; 4]; 4],
+}
+
+/// (触发条件, 动作) 在 `rules` 中的位
+fn rule_bit(provocation: Provocation, action: Action) -> u128 {
+    1 << (provocation as u32 * 9 + action as u32)
+}
+
+impl SyncActions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `trigger` 的 `provocation` 成立时，`targets` 中的各轴执行 `action`
+    pub fn on(
+        mut self,
+        trigger: Axis,
+        provocation: Provocation,
+        targets: impl Into<Axes>,
+        action: Action,
+    ) -> Self {
+        let targets = targets.into();
+        self.words[trigger.index()] |=
+            provocation.bit() | u32::from(targets.bits()) << ACTION_AXES_SHIFT;
+        for target in targets.iter() {
+            self.words[target.index()] |= action.bit();
+            self.rules[trigger.index()][target.index()] |= rule_bit(provocation, action);
+        }
+        self.axes = self.axes | trigger | targets;
+        self
+    }
+
+    /// 清除 `axis` 的同步动作，写入时该轴被设为无动作
+    pub fn clear(mut self, axis: Axis) -> Self {
+        self.words[axis.index()] = 0;
+        self.rules[axis.index()] = [0; 4];
+        for rules in &mut self.rules {
+            rules[axis.index()] = 0;
+        }
+        self.axes = self.axes | axis;
+        self
+    }
+
+    /// 设定涉及的轴
+    pub fn axes(&self) -> Axes {
+        self.axes
+    }
+
+    /// 写入 `axis` 的设定值，低 16 位为触发条件与动作轴，高 16 位为动作
+    pub fn word(&self, axis: Axis) -> u32 {
+        self.words[axis.index()]
+    }
+
+    /// 写入时的各条设定命令：设定值相同的轴合并为一条，以轴掩码同时写入
+    pub(crate) fn writes(&self) -> Vec<(Axes, u32)> {
+        let mut writes: Vec<(Axes, u32)> = Vec::new();
+        for axis in self.axes.iter() {
+            let word = self.word(axis);
+            match writes.iter_mut().find(|(_, w)| *w == word) {
+                Some((axes, _)) => *axes = *axes | axis,
+                None => writes.push((axis.into(), word)),
+            }
+        }
+        writes
+    }
+
+    fn actions(&self, axis: Axis) -> impl Iterator<Item = Action> + '_ {
+        Action::ALL
+            .into_iter()
+            .filter(move |action| self.word(axis) & action.bit() != 0)
+    }
+
+    /// 写入后芯片实际会执行、却不是由 [`on`](Self::on) 要求的一项同步动作
+    fn unintended(&self) -> Option<(Axis, Provocation, Axis, Action)> {
+        for trigger in self.axes.iter() {
+            let word = self.word(trigger);
+            let targets = Axes::from_bits((word >> ACTION_AXES_SHIFT) as u8);
+            for provocation in Provocation::ALL {
+                if word & provocation.bit() == 0 {
+                    continue;
+                }
+                for target in targets.iter() {
+                    let requested = self.rules[trigger.index()][target.index()];
+                    if let Some(action) = self
+                        .actions(target)
+                        .find(|action| requested & rule_bit(provocation, *action) == 0)
+                    {
+                        return Some((trigger, provocation, target, action));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// 检查每个轴至多有一个驱动动作与一个锁存动作，且写入后不会执行未要求的动作
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        for axis in self.axes.iter() {
+            let conflict = |select: fn(Action) -> bool| {
+                let mut actions = self.actions(axis).filter(|action| select(*action));
+                match (actions.next(), actions.next()) {
+                    (Some(first), Some(second)) => Err(Error::ConflictingSyncActions {
+                        axis,
+                        first,
+                        second,
+                    }),
+                    _ => Ok(()),
+                }
+            };
+            conflict(Action::is_drive)?;
+            conflict(Action::is_latch)?;
+        }
+        match self.unintended() {
+            Some((trigger, provocation, target, action)) => Err(Error::UnintendedSyncAction {
+                trigger,
+                provocation,
+                target,
+                action,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 在一个事务中写入各轴的同步动作设定，未出现在 `actions` 中的轴保持原设定
+    pub fn set_sync_actions(&self, actions: &SyncActions) -> Result<(), Error> {
+        let apply = || {
+            actions.check()?;
+            let mut transaction = self.transaction();
+            for (axes, word) in actions.writes() {
+                transaction.write_data(axes, SYNC_SET, word);
+            }
+            transaction.flush().map(|_| ())
+        };
+        apply().during("set_sync_actions", None)
+    }
+
+    /// 清除指定轴的同步动作
+    pub fn clear_sync_actions(&self, axes: Axes) -> Result<(), Error> {
+        let mut transaction = self.transaction();
+        transaction.write_data(axes, SYNC_SET, 0);
+        transaction
+            .flush()
+            .map(|_| ())
+            .during("clear_sync_actions", None)
+    }
+
+    /// 发出同步动作启动命令，触发以 [`Provocation::Command`] 为条件的动作
+    pub fn activate_sync(&self, axes: Axes) -> Result<(), Error> {
+        let mut transaction = self.transaction();
+        transaction.command(axes, SYNC_ACTIVATE);
+        transaction
+            .flush()
+            .map(|_| ())
+            .during("activate_sync", None)
+    }
+
+    /// 读取由 [`Action::LatchLogicPosition`] 或 [`Action::LatchRealPosition`] 锁存的位置
+    pub fn read_latched(&self, axis: Axis) -> Result<i32, Error> {
+        Ok(self.read_br(axis)? as i32)
+    }
+}
@@ -80,3 +80,993 @@ fn register_parse() {
         }
     );
 }
+
+#[test]
+fn transaction_optimize() {
+    use crate::transaction::{optimize, Operation::*};
+
+    let operations = [
+        WritePipe { pipe: 0, data: 0x8000 },
+        WritePipe { pipe: 0, data: 0x010f },
+        ReadRegister(1),
+        WritePipe { pipe: 0, data: 0x010f },
+        ReadRegister(2),
+        WritePipe { pipe: 7, data: 0 },
+        WritePipe { pipe: 6, data: 0 },
+        WritePipe { pipe: 0, data: 0x0f09 },
+        WritePipe { pipe: 7, data: 0 },
+        WritePipe { pipe: 6, data: 0 },
+        WritePipe { pipe: 0, data: 0x0f0a },
+        WritePipe { pipe: 0, data: 0x020f },
+        WritePipe { pipe: 0, data: 0x8000 },
+        WritePipe { pipe: 7, data: 0 },
+    ];
+    assert_eq!(
+        optimize(&operations),
+        [
+            WritePipe { pipe: 0, data: 0x8000 },
+            WritePipe { pipe: 0, data: 0x010f },
+            ReadRegister(1),
+            ReadRegister(2),
+            WritePipe { pipe: 7, data: 0 },
+            WritePipe { pipe: 6, data: 0 },
+            WritePipe { pipe: 0, data: 0x0f09 },
+            WritePipe { pipe: 0, data: 0x0f0a },
+            WritePipe { pipe: 0, data: 0x020f },
+            WritePipe { pipe: 0, data: 0x8000 },
+            WritePipe { pipe: 7, data: 0 },
+        ]
+    );
+}
+
+#[test]
+fn transaction_transfers() {
+    use crate::transaction::{optimize, transfers, Operation::*};
+
+    // 四个轴写入相同的驱动速度：数据寄存器只需写入一次
+    let mut operations = Vec::new();
+    for axis in [0x0100, 0x0200, 0x0400, 0x0800] {
+        operations.extend([
+            WritePipe {
+                pipe: 7,
+                data: 1000,
+            },
+            WritePipe { pipe: 6, data: 0 },
+            WritePipe {
+                pipe: 0,
+                data: axis | 0x05,
+            },
+        ]);
+    }
+    operations.push(ReadRegister(0));
+    assert_eq!(transfers(&operations), 27);
+    assert_eq!(transfers(&optimize(&operations)), 15);
+}
+
+#[test]
+fn policy_retry() {
+    use crate::policy::UsbPolicy;
+    use std::time::Duration;
+
+    let policy = UsbPolicy {
+        retries: 2,
+        backoff: Duration::ZERO,
+        ..UsbPolicy::default()
+    };
+
+    let mut attempts = 0;
+    let mut halts = 0;
+    let result = policy.run(
+        |_| {
+            attempts += 1;
+            match attempts {
+                1 => Err(rusb::Error::Pipe),
+                2 => Err(rusb::Error::Timeout),
+                _ => Ok(attempts),
+            }
+        },
+        || {
+            halts += 1;
+            Ok(())
+        },
+    );
+    assert_eq!(result, Ok(3));
+    assert_eq!(halts, 1);
+
+    let mut attempts = 0;
+    let result: rusb::Result<()> = policy.run(
+        |_| {
+            attempts += 1;
+            Err(rusb::Error::Timeout)
+        },
+        || Ok(()),
+    );
+    assert_eq!(result, Err(rusb::Error::Timeout));
+    assert_eq!(attempts, 3);
+
+    let result: rusb::Result<()> = policy.run(|_| Err(rusb::Error::NoDevice), || Ok(()));
+    assert_eq!(result, Err(rusb::Error::NoDevice));
+
+    // 超时的写入可能已部分送出，不重试
+    let mut attempts = 0;
+    let result: rusb::Result<()> = policy.run_write(
+        |_| {
+            attempts += 1;
+            Err(rusb::Error::Timeout)
+        },
+        || Ok(()),
+    );
+    assert_eq!(result, Err(rusb::Error::Timeout));
+    assert_eq!(attempts, 1);
+
+    // STALL 的写入未被接收，清除 halt 后重试
+    let mut attempts = 0;
+    let result = policy.run_write(
+        |_| {
+            attempts += 1;
+            match attempts {
+                1 => Err(rusb::Error::Pipe),
+                _ => Ok(attempts),
+            }
+        },
+        || Ok(()),
+    );
+    assert_eq!(result, Ok(2));
+
+    let no_clear = UsbPolicy {
+        clear_halt: false,
+        ..policy
+    };
+    let result: rusb::Result<()> = no_clear.run_write(|_| Err(rusb::Error::Pipe), || Ok(()));
+    assert_eq!(result, Err(rusb::Error::Pipe));
+}
+
+#[test]
+fn device_path() {
+    use crate::device::DeviceInfo;
+
+    assert_eq!(DeviceInfo::parse_path("1-3.2"), Some((1, vec![3, 2])));
+    assert_eq!(DeviceInfo::parse_path("2-1"), Some((2, vec![1])));
+    assert_eq!(DeviceInfo::parse_path("1-"), None);
+    assert_eq!(DeviceInfo::parse_path("1.3"), None);
+
+    let info = DeviceInfo {
+        bus: 1,
+        port_path: vec![3, 2],
+        address: 7,
+        serial: None,
+    };
+    assert_eq!(info.path(), "1-3.2");
+}
+
+#[test]
+fn axis_error_cause() {
+    use crate::common::{Axis, Direction};
+    use crate::Error;
+
+    let mut rr2 = RR2 {
+        software_limit_plus: false,
+        software_limit_minus: false,
+        hardware_limit_plus: false,
+        hardware_limit_minus: false,
+        servo_alarm: false,
+        emergency: false,
+        home_error: false,
+        home_step: AutoHomeSearchStep::Step0,
+    };
+    assert_eq!(rr2.check(Axis::X), Ok(()));
+
+    rr2.software_limit_minus = true;
+    assert_eq!(
+        rr2.check(Axis::Y),
+        Err(Error::LimitHit {
+            axis: Axis::Y,
+            direction: Direction::Minus,
+            software: true,
+        })
+    );
+
+    rr2.emergency = true;
+    let error = Error::Operation {
+        operation: "wait_idle",
+        axis: Some(Axis::Z),
+        source: Box::new(rr2.check(Axis::Z).unwrap_err()),
+    };
+    assert_eq!(error.cause(), &Error::EmergencyStop { axis: Axis::Z });
+    assert_eq!(
+        error.to_string(),
+        "wait_idle on Z axis failed: Z axis stopped by emergency stop"
+    );
+}
+
+#[test]
+fn shared_controller_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<crate::shared::SharedController>();
+    assert_send_sync::<crate::shared::SharedController<rusb::Context>>();
+}
+
+#[test]
+fn axes_mask() {
+    use crate::common::{Axes, Axis};
+
+    let axes = Axis::X | Axis::Z;
+    assert_eq!(axes.bits(), 0x05);
+    assert!(axes.contains(Axis::Z));
+    assert!(!axes.contains(Axis::U));
+    assert_eq!(axes.iter().collect::<Vec<_>>(), [Axis::X, Axis::Z]);
+    assert_eq!(Axis::ALL.into_iter().collect::<Axes>(), Axes::ALL);
+    assert_eq!(Axes::from_bits(0xf2), Axes::from(Axis::Y));
+}
+
+#[test]
+fn wait_poll() {
+    use crate::wait::{CancellationToken, WaitOptions};
+    use crate::Error;
+    use std::time::Duration;
+
+    let options = WaitOptions {
+        poll_interval: Duration::from_millis(1),
+        cancel: Some(CancellationToken::new()),
+    };
+
+    let mut polls = 0;
+    let result = options.poll(Duration::from_secs(1), || {
+        polls += 1;
+        Ok((polls == 3).then_some(polls))
+    });
+    assert_eq!(result, Ok(3));
+
+    let timeout = Duration::from_millis(5);
+    let result: Result<(), Error> = options.poll(timeout, || Ok(None));
+    assert_eq!(result, Err(Error::WaitTimeout(timeout)));
+
+    options.cancel.as_ref().unwrap().cancel();
+    let result: Result<(), Error> = options.poll(Duration::from_secs(1), || Ok(None));
+    assert_eq!(result, Err(Error::Cancelled));
+}
+
+#[test]
+fn snapshot_display() {
+    use crate::snapshot::{AxisSnapshot, ControllerSnapshot};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let axis = |position: i32| AxisSnapshot {
+        status: RR1 {
+            comp_plus: false,
+            comp_minus: false,
+            v_ascending: false,
+            v_const: true,
+            v_descending: false,
+            a_ascending: false,
+            a_const: false,
+            a_descending: false,
+            in0: false,
+            in1: false,
+            in2: false,
+            in3: false,
+            limit_plus: true,
+            limit_minus: false,
+            servo_alarm: false,
+            emergency: false,
+        },
+        error: RR2 {
+            software_limit_plus: false,
+            software_limit_minus: false,
+            hardware_limit_plus: true,
+            hardware_limit_minus: false,
+            servo_alarm: false,
+            emergency: false,
+            home_error: false,
+            home_step: AutoHomeSearchStep::Step0,
+        },
+        signals: AxisExternalSignal {
+            in0: true,
+            in1: false,
+            in2: false,
+            in3: false,
+            p_p: false,
+            p_m: false,
+            in_position: true,
+            servo_alarm: false,
+        },
+        logic_position: position,
+        real_position: -position,
+        current_speed: 100,
+        current_acceleration: 0,
+    };
+    let snapshot = ControllerSnapshot {
+        timestamp: UNIX_EPOCH + Duration::from_millis(1_500),
+        main: RR0 {
+            x_drv: true,
+            y_drv: false,
+            z_drv: false,
+            u_drv: false,
+            x_error: false,
+            y_error: false,
+            z_error: false,
+            u_error: false,
+            i_drv: false,
+            c_next: false,
+            zone: 0,
+            bit_interpolate_stack_counter: 0,
+        },
+        axes: [axis(1), axis(2), axis(3), axis(4)],
+    };
+
+    let text = snapshot.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "USB1020 snapshot at 1.500 (unix time)");
+    assert_eq!(
+        lines[3],
+        "driving                    yes             -             -             -  # 驱动状态 (RR0)"
+    );
+    assert_eq!(
+        lines[6],
+        "real position               -1            -2            -3            -4  # 实位计数器 EP"
+    );
+    assert!(lines[13].starts_with("inputs") && lines[13].contains(" IN0,INPOS "));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_representation() {
+    use crate::common::{Axes, Axis, CounterType, OneOrAllAxis};
+    use serde_json::json;
+
+    assert_eq!(serde_json::to_value(Axis::X).unwrap(), json!("x"));
+    assert_eq!(
+        serde_json::to_value(OneOrAllAxis::One(Axis::Y)).unwrap(),
+        json!({ "one": "y" })
+    );
+    assert_eq!(
+        serde_json::to_value(OneOrAllAxis::All).unwrap(),
+        json!("all")
+    );
+    assert_eq!(serde_json::to_value(Axis::X | Axis::U).unwrap(), json!(9));
+    assert_eq!(
+        serde_json::to_value(CounterType::Logic).unwrap(),
+        json!("logic")
+    );
+    assert_eq!(
+        serde_json::to_value(AutoHomeSearchStep::Step3).unwrap(),
+        json!("step3")
+    );
+    assert_eq!(
+        serde_json::from_value::<Axes>(json!(6)).unwrap(),
+        Axis::Y | Axis::Z
+    );
+    assert!(serde_json::from_value::<Axes>(json!(0x10)).is_err());
+    assert!(Axes::try_from(0xf2).is_err());
+
+    let rr2 = RR2 {
+        software_limit_plus: false,
+        software_limit_minus: false,
+        hardware_limit_plus: true,
+        hardware_limit_minus: false,
+        servo_alarm: false,
+        emergency: false,
+        home_error: false,
+        home_step: AutoHomeSearchStep::Step0,
+    };
+    let value = serde_json::to_value(&rr2).unwrap();
+    assert_eq!(value["hardware_limit_plus"], json!(true));
+    assert_eq!(value["home_step"], json!("step0"));
+    assert_eq!(serde_json::from_value::<RR2>(value).unwrap(), rr2);
+}
+
+#[test]
+fn config_validate() {
+    use crate::common::{Axis, LineOrCurve};
+    use crate::config::{AxisConfig, DriveConfig, MachineConfig};
+
+    // 默认配置与初始化写入的 WR2 一致
+    assert_eq!(AxisConfig::default().wr2(), 0x0100);
+
+    let mut config = MachineConfig::default();
+    config.axis.insert(
+        Axis::Y,
+        AxisConfig {
+            drive: DriveConfig {
+                start_speed: 2000,
+                speed: 1000,
+                curve: LineOrCurve::SCurve,
+                ..DriveConfig::default()
+            },
+            ..AxisConfig::default()
+        },
+    );
+    let errors = config.validate().unwrap_err();
+    let keys: Vec<&str> = errors.iter().map(|error| error.key.as_str()).collect();
+    assert_eq!(keys, ["axis.y.drive.start_speed", "axis.y.drive.jerk"]);
+    assert_eq!(errors[0].line, None);
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn config_from_toml() {
+    use crate::common::Axis;
+    use crate::config::{HomingConfig, MachineConfig, StopMode};
+    use crate::Error;
+
+    let text = r#"
+[axis.x]
+name = "table"
+pulse_mode = "pulse_direction"
+
+[axis.x.scale]
+steps_per_unit = 800.0
+
+[axis.x.limits]
+stop = "decel"
+software_plus = 100000
+software_minus = -100000
+
+[axis.x.drive]
+range = 800000
+speed = 4000
+acceleration = 200
+"#;
+    let config = MachineConfig::from_toml(text).unwrap();
+    let x = &config.axis[&Axis::X];
+    assert_eq!(x.name.as_deref(), Some("table"));
+    assert_eq!(x.limits.stop, StopMode::Decel);
+    assert_eq!(x.wr2() & 0b111, 0b111);
+    assert_eq!(
+        MachineConfig::from_toml(&config.to_toml().unwrap()),
+        Ok(config)
+    );
+
+    let text = r#"
+[axis.z.drive]
+range = 16000
+start_speed = 2000
+speed = 1000
+"#;
+    match MachineConfig::from_toml(text) {
+        Err(Error::InvalidConfig(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].key, "axis.z.drive.start_speed");
+            assert_eq!(errors[0].line, Some(4));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(
+        MachineConfig::from_toml("[axis.w]\n"),
+        Err(Error::ConfigSyntax(_))
+    ));
+
+    // 反向搜寻 IN0、IN1，再正向移动 500 个脉冲
+    let text = r#"
+[axis.x.homing]
+direction = "minus"
+speed = 100
+offset = 500
+"#;
+    let config = MachineConfig::from_toml(text).unwrap();
+    let homing = config.axis[&Axis::X].homing.unwrap();
+    assert_eq!(homing.mode(), 0b0100_1111);
+    assert_eq!(
+        HomingConfig {
+            offset: 0,
+            ..homing
+        }
+        .mode(),
+        0b1111
+    );
+
+    // 偏移量与外部定长驱动共用 P
+    let text = r#"
+[axis.x.homing]
+direction = "plus"
+speed = 9000
+offset = -10
+
+[axis.x.external_drive]
+mode = "fixed_pulse"
+"#;
+    match MachineConfig::from_toml(text) {
+        Err(Error::InvalidConfig(errors)) => {
+            let keys: Vec<_> = errors.iter().map(|error| error.key.as_str()).collect();
+            assert_eq!(keys, ["axis.x.homing.speed", "axis.x.homing.offset"]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn axis_scale() {
+    use crate::common::Direction;
+    use crate::scale::AxisScale;
+    use crate::Error;
+
+    let scale = AxisScale {
+        steps_per_unit: 200.0,
+        gear_numerator: 3,
+        gear_denominator: 2,
+        invert: true,
+        encoder_counts_per_unit: Some(1000.0),
+    };
+    assert_eq!(scale.pulses_per_unit(), 300.0);
+    assert_eq!(scale.to_pulses(2.5), Ok(-750));
+    assert_eq!(scale.from_pulses(-750), 2.5);
+    assert_eq!(scale.to_counts(1.0), Ok(-1000));
+    assert_eq!(scale.from_counts(500), -0.5);
+    assert_eq!(scale.direction(Direction::Plus), Direction::Minus);
+
+    // 10 mm/s = 3000 pps，倍率 M = 2 时 V = 1500
+    assert_eq!(scale.speed_register(10.0, 2.0), Ok(1500));
+    // 100 mm/s² = 30000 pps/s，A = 30000 / (125 × 2) = 120
+    assert_eq!(scale.acceleration_register(100.0, 2.0), Ok(120));
+    assert_eq!(
+        scale.speed_register(1000.0, 1.0),
+        Err(Error::OutOfRange {
+            quantity: "speed",
+            value: 1000.0
+        })
+    );
+    assert!(scale.to_pulses(1e9).is_err());
+}
+
+#[test]
+fn speed_planner() {
+    use crate::common::LineOrCurve;
+    use crate::planner::SpeedRequest;
+    use crate::Error;
+
+    // 10 kpps 在 M = 1 时超出 V 的上限，取 M = 1.25 即可精确达到
+    let request = SpeedRequest {
+        start_speed: 500.0,
+        speed: 10_000.0,
+        acceleration: 100_000.0,
+        deceleration: None,
+        jerk: None,
+    };
+    let plan = request.plan().unwrap();
+    assert_eq!(plan.drive.range, 6_400_000);
+    assert_eq!(plan.drive.speed, 8000);
+    assert_eq!(plan.drive.start_speed, 400);
+    assert_eq!(plan.drive.acceleration, 640);
+    assert_eq!(plan.drive.curve, LineOrCurve::Line);
+    assert_eq!(plan.actual.speed, 10_000.0);
+    assert!(plan.error < 1e-9);
+
+    let plan = SpeedRequest {
+        jerk: Some(1_000_000.0),
+        ..request
+    }
+    .plan()
+    .unwrap();
+    assert_eq!(plan.drive.curve, LineOrCurve::SCurve);
+    assert!(plan.error < 0.01, "{:?}", plan);
+
+    assert_eq!(
+        SpeedRequest {
+            speed: 5_000_000.0,
+            ..request
+        }
+        .plan(),
+        Err(Error::OutOfRange {
+            quantity: "speed",
+            value: 5_000_000.0
+        })
+    );
+    // 初始速度 1 pps 要求 M ≤ 1，驱动速度 1 Mpps 要求 M ≥ 125
+    assert_eq!(
+        SpeedRequest {
+            start_speed: 1.0,
+            speed: 1_000_000.0,
+            ..request
+        }
+        .plan(),
+        Err(Error::IncompatibleSpeeds {
+            first: "speed",
+            second: "start speed"
+        })
+    );
+}
+
+#[test]
+fn motion_profile() {
+    use crate::common::{DecelerationMode, LineOrCurve};
+    use crate::config::DriveConfig;
+    use crate::profile::Profile;
+    use crate::Error;
+
+    // M = 1：100 pps 起步，以 10,000 pps/s 加速到 1,000 pps，加速段 0.09 s、49.5 个脉冲
+    let drive = DriveConfig {
+        start_speed: 100,
+        speed: 1000,
+        acceleration: 80,
+        ..DriveConfig::default()
+    };
+    let profile = Profile::new(&drive, 1000).unwrap();
+    assert_eq!(profile.mode, DecelerationMode::Auto);
+    assert!(!profile.triangle);
+    assert_eq!(profile.peak_speed, 1000.0);
+    assert_eq!(profile.decel_point, 950);
+    assert!((profile.duration - 1.081).abs() < 1e-9);
+    let end = profile.sample(profile.duration);
+    assert!((end.position - 1000.0).abs() < 1e-6);
+
+    // 距离不足时成为三角形曲线：峰值² = SV² + 2·P·A·D / (A + D)
+    let short = Profile::new(&drive, 50).unwrap();
+    assert!(short.triangle);
+    assert!((short.peak_speed - 510_000f64.sqrt()).abs() < 1e-6);
+    assert_eq!(short.decel_point, 25);
+
+    let s_curve = Profile::new(
+        &DriveConfig {
+            jerk: Some(625),
+            curve: LineOrCurve::SCurve,
+            ..drive
+        },
+        1000,
+    )
+    .unwrap();
+    assert!(!s_curve.triangle);
+    assert!(s_curve.duration > profile.duration);
+    assert!((s_curve.sample(s_curve.duration).position - 1000.0).abs() < 1e-6);
+
+    // 减速点过早时以初始速度走完剩余的脉冲
+    let manual = Profile::manual(&drive, 1000, 500).unwrap();
+    assert_eq!(manual.mode, DecelerationMode::Manual);
+    assert!(manual.duration > profile.duration);
+    assert!((manual.sample(manual.duration).position - 1000.0).abs() < 1e-6);
+
+    let csv = profile.to_csv(0.1).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("time,position,velocity,acceleration"));
+    assert_eq!(lines.next(), Some("0.000000,0.000,100.000,10000.000"));
+    assert_eq!(lines.last(), Some("1.081000,1000.000,0.000,0.000"));
+    assert!(matches!(
+        profile.samples(0.0),
+        Err(Error::OutOfRange { .. })
+    ));
+    assert_eq!(
+        profile.samples(1e-12).map(|samples| samples.len()),
+        Err(Error::OutOfRange {
+            quantity: "sample interval",
+            value: 1e-12
+        })
+    );
+
+    let stalled = DriveConfig {
+        acceleration: 0,
+        ..drive
+    };
+    match Profile::new(&stalled, 1000) {
+        Err(Error::InvalidConfig(errors)) => assert_eq!(errors[0].key, "drive.acceleration"),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn manual_deceleration() {
+    use crate::common::LineOrCurve;
+    use crate::config::DriveConfig;
+    use crate::profile::{ManualDeceleration, Profile};
+
+    // 减速段 0.09 s、49.5 个脉冲，理想减速点为 950；M = 1，AO = ⌈1 × 0.09 / 2⌉ = 1
+    let drive = DriveConfig {
+        start_speed: 100,
+        speed: 1000,
+        acceleration: 80,
+        ..DriveConfig::default()
+    };
+    let manual = ManualDeceleration::new(&drive, 1000).unwrap();
+    assert_eq!(manual.offset, 1);
+    assert_eq!(manual.decel_point, 949);
+
+    // M = 500：5,000 pps 到 50,000 pps，减速度 5,000,000 pps/s，减速段 0.009 s、247.5 个脉冲，
+    // 理想减速点为 9,752；AO = ⌈500 × 0.009 / 2⌉ = 3
+    let coarse = DriveConfig {
+        range: 16_000,
+        start_speed: 10,
+        speed: 100,
+        ..drive
+    };
+    let manual = ManualDeceleration::new(&coarse, 10_000).unwrap();
+    assert_eq!(manual.offset, 3);
+    assert_eq!(manual.decel_point, 9749);
+
+    // 非对称 S 曲线：减速度为加速度的一半，减速段更长
+    let asymmetric = DriveConfig {
+        deceleration: Some(40),
+        jerk: Some(625),
+        curve: LineOrCurve::SCurve,
+        ..drive
+    };
+    let manual = ManualDeceleration::with_offset(&asymmetric, 1000, 0).unwrap();
+    let profile = Profile::manual(&asymmetric, 1000, manual.decel_point).unwrap();
+    let end = profile.sample(profile.duration - 1e-9);
+    assert!((end.velocity - 100.0).abs() < 1.0, "{:?}", end);
+    assert!(manual.decel_point < 900);
+
+    // 提前量超过理想减速点时从头开始减速
+    let manual = ManualDeceleration::with_offset(&drive, 4, 8).unwrap();
+    assert_eq!(manual.decel_point, 0);
+
+    // 写入新的速度曲线时清除 MANLD，保留 EXOP 与输入滤波等其余位
+    assert_eq!(asymmetric.wr3(0x2000 | 0b1_0001), 0x2000 | 0b1_0110);
+    assert_eq!(drive.wr3(0b111), 0);
+}
+
+#[test]
+fn drive_change_phases() {
+    use crate::drive::{Change, DriveChange};
+    use crate::register::RR1;
+
+    let status = |v: [bool; 3], a: [bool; 3]| RR1 {
+        comp_plus: false,
+        comp_minus: false,
+        v_ascending: v[0],
+        v_const: v[1],
+        v_descending: v[2],
+        a_ascending: a[0],
+        a_const: a[1],
+        a_descending: a[2],
+        in0: false,
+        in1: false,
+        in2: false,
+        in3: false,
+        limit_plus: false,
+        limit_minus: false,
+        servo_alarm: false,
+        emergency: false,
+    };
+    let none = [false; 3];
+
+    // 直线加速、定速中都可以改写
+    let linear_accel = status([true, false, false], none);
+    assert!(linear_accel.accepts_speed_change());
+    assert!(linear_accel.accepts_pulse_change());
+    let constant = status([false, true, false], none);
+    assert!(constant.accepts_speed_change());
+
+    // S 曲线加速中只能改写输出脉冲数
+    let s_accel = status([true, false, false], [false, true, false]);
+    assert!(!s_accel.accepts_speed_change());
+    assert!(s_accel.accepts_pulse_change());
+
+    // 减速中都不能改写
+    let decel = status([false, false, true], none);
+    assert!(!decel.accepts_speed_change());
+    assert!(!decel.accepts_pulse_change());
+
+    // 停止时写入的值用于下一次驱动
+    assert!(status(none, none).accepts_speed_change());
+
+    // 未在驱动时不写入；连续驱动中不改写输出脉冲数
+    let idle = DriveChange::decide(status(none, none), false, false, false, true, false);
+    assert_eq!((idle.speed, idle.pulses), (Change::Idle, Change::Unchanged));
+    let continuous = DriveChange::decide(constant.clone(), true, true, false, true, true);
+    assert_eq!(
+        (continuous.speed, continuous.pulses),
+        (Change::Applied, Change::Rejected)
+    );
+    let fixed = DriveChange::decide(constant.clone(), true, false, false, false, true);
+    assert_eq!(fixed.pulses, Change::Applied);
+
+    // S 曲线驱动在定速中也不改写驱动速度
+    let s_curve = DriveChange::decide(constant, true, false, true, true, true);
+    assert_eq!(
+        (s_curve.speed, s_curve.pulses),
+        (Change::Rejected, Change::Applied)
+    );
+}
+
+#[test]
+fn move_group_validation() {
+    use crate::common::{Axis, Direction};
+    use crate::config::DriveConfig;
+    use crate::drive::command_word;
+    use crate::group::{validate, AxisMove};
+    use crate::Error;
+
+    let x = AxisMove {
+        axis: Axis::X,
+        drive: DriveConfig::default(),
+        pulses: 1000,
+        direction: Direction::Plus,
+    };
+    let moves = [
+        x,
+        AxisMove {
+            axis: Axis::Y,
+            direction: Direction::Minus,
+            ..x
+        },
+        AxisMove { axis: Axis::Z, ..x },
+    ];
+    let axes = validate(&moves).unwrap();
+    assert_eq!(axes.bits(), 0x07);
+    // 以一条带轴掩码的命令解除保持
+    assert_eq!(command_word(axes, 0x25), 0x0725);
+
+    let bad = [
+        x,
+        AxisMove {
+            drive: DriveConfig {
+                speed: 9000,
+                ..DriveConfig::default()
+            },
+            ..x
+        },
+    ];
+    match validate(&bad) {
+        Err(Error::InvalidConfig(errors)) => {
+            let keys: Vec<_> = errors.iter().map(|e| e.key.as_str()).collect();
+            assert_eq!(keys, ["axis.x", "axis.x.drive.speed"]);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn sync_actions() {
+    use crate::common::Axis;
+    use crate::sync::{Action, Provocation, SyncActions};
+    use crate::Error;
+
+    let actions = SyncActions::new()
+        .on(
+            Axis::X,
+            Provocation::In3Rising,
+            Axis::X,
+            Action::LatchRealPosition,
+        )
+        .on(Axis::X, Provocation::In3Rising, Axis::X, Action::Interrupt)
+        .on(
+            Axis::Y,
+            Provocation::CompPlusReached,
+            Axis::Y,
+            Action::DecelStop,
+        )
+        .clear(Axis::Z);
+    assert_eq!(actions.axes().bits(), 0x07);
+    // 触发条件与动作轴在低 16 位，动作在高 16 位
+    assert_eq!(
+        actions.word(Axis::X),
+        0x0040 | 0x1000 | 0x0080_0000 | 0x0100_0000
+    );
+    assert_eq!(actions.word(Axis::Y), 0x0001 | 0x2000 | 0x0010_0000);
+    assert_eq!(actions.word(Axis::Z), 0);
+    assert!(actions.check().is_ok());
+
+    // 同一触发轴的两条设定相互牵连：COMP+ 也会使 Z 停止
+    let entangled = SyncActions::new()
+        .on(
+            Axis::X,
+            Provocation::CompPlusReached,
+            Axis::Y,
+            Action::FixedDrivePlus,
+        )
+        .on(Axis::X, Provocation::DriveEnd, Axis::Z, Action::DecelStop);
+    assert_eq!(
+        entangled.check(),
+        Err(Error::UnintendedSyncAction {
+            trigger: Axis::X,
+            provocation: Provocation::CompPlusReached,
+            target: Axis::Z,
+            action: Action::DecelStop,
+        })
+    );
+
+    // X 越过 COMP+ 时 Y、U 开始定长驱动：触发条件写在 X 上，动作以 Y、U 的掩码一次写入
+    let cross = SyncActions::new().on(
+        Axis::X,
+        Provocation::CompPlusReached,
+        Axis::Y | Axis::U,
+        Action::FixedDrivePlus,
+    );
+    assert_eq!(cross.axes().bits(), 0x0b);
+    assert_eq!(
+        cross.writes(),
+        [
+            (Axis::X.into(), 0x0001 | 0xa000),
+            (Axis::Y | Axis::U, 0x0001_0000)
+        ]
+    );
+
+    let conflicting = actions.on(
+        Axis::Z,
+        Provocation::DriveEnd,
+        Axis::Y,
+        Action::FixedDrivePlus,
+    );
+    assert_eq!(
+        conflicting.check(),
+        Err(Error::ConflictingSyncActions {
+            axis: Axis::Y,
+            first: Action::FixedDrivePlus,
+            second: Action::DecelStop,
+        })
+    );
+}
+
+#[test]
+fn probe_settings() {
+    use crate::common::{CounterType, Direction};
+    use crate::config::{ActiveLevel, DriveConfig, StopMode};
+    use crate::probe::{Probe, ProbeInput};
+
+    let probe = Probe {
+        input: ProbeInput::In2,
+        level: ActiveLevel::High,
+        stop: StopMode::Decel,
+        counter: CounterType::Fact,
+        direction: Direction::Minus,
+        drive: DriveConfig {
+            start_speed: 10,
+            speed: 200,
+            ..DriveConfig::default()
+        },
+        max_distance: Some(10_000),
+    };
+    assert_eq!(probe.effective_drive(), probe.drive);
+
+    // 立即停止时以驱动速度起步，使 IN 信号立即停止驱动
+    let sudden = Probe {
+        stop: StopMode::Sudden,
+        ..probe
+    };
+    assert_eq!(sudden.effective_drive().start_speed, 200);
+    assert!(sudden.effective_drive().problems().is_empty());
+
+    // IN2 的有效电平位 D4 与停止允许位 D5；IN0 低电平有效时只置停止允许位
+    assert_eq!(ProbeInput::In2.wr1_bits(ActiveLevel::High), (0x30, 0x30));
+    assert_eq!(ProbeInput::In0.wr1_bits(ActiveLevel::Low), (0x03, 0x02));
+}
+
+#[test]
+fn external_drive() {
+    use crate::common::Axis;
+    use crate::config::{AxisConfig, ExternalDriveConfig, ExternalDriveMode, MachineConfig};
+
+    // EXOP 位于 WR3 D3～D4，其余位保持不变
+    let wr3 = |mode| ExternalDriveConfig { mode, pulses: 1 }.wr3(0x0f1f);
+    assert_eq!(wr3(ExternalDriveMode::Disabled), 0x0f07);
+    assert_eq!(wr3(ExternalDriveMode::Continuous), 0x0f0f);
+    assert_eq!(wr3(ExternalDriveMode::FixedPulse), 0x0f17);
+    assert_eq!(wr3(ExternalDriveMode::ManualPulser), 0x0f1f);
+
+    let mut config = MachineConfig::default();
+    config.axis.insert(
+        Axis::U,
+        AxisConfig {
+            external_drive: ExternalDriveConfig {
+                mode: ExternalDriveMode::FixedPulse,
+                pulses: 0,
+            },
+            ..AxisConfig::default()
+        },
+    );
+    let errors = config.validate().unwrap_err();
+    assert_eq!(errors[0].key, "axis.u.external_drive.pulses");
+    // 连续方式不使用 P
+    let continuous = ExternalDriveConfig {
+        mode: ExternalDriveMode::Continuous,
+        pulses: 0,
+    };
+    assert!(continuous.problems().is_empty());
+}
+
+#[test]
+fn input_filter() {
+    use crate::config::{FilterTime, InputFilterConfig};
+    use std::time::Duration;
+
+    // 默认值与初始化写入的 WR3 一致
+    assert_eq!(InputFilterConfig::default().wr3(0), 0x0f00);
+
+    let filter = InputFilterConfig {
+        servo: false,
+        in3: true,
+        time: FilterTime::Us896,
+        ..InputFilterConfig::default()
+    };
+    // FE0、FE1、FE3、FE4 与 FL = 3，低字节保持不变
+    assert_eq!(filter.wr3(0x0f1f), 0x7b1f);
+    assert_eq!(filter.time.duration(), Duration::from_micros(896));
+}
@@ -0,0 +1,206 @@
+use crate::common::{Axes, Axis};
+use crate::{During, Error, USB1020Controller};
+use deku::prelude::*;
+use rusb::{GlobalContext, UsbContext};
+
+/// 事务中排队的一次操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    WritePipe { pipe: u8, data: u16 },
+    ReadRegister(u8),
+}
+
+/// 读寄存器操作在事务结果中的位置，由 [`Transaction::read_rr`] 返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ticket(usize);
+
+/// 32 位数据读取在事务结果中的位置，由 [`Transaction::read_u32_data`] 返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct U32Ticket(Ticket, Ticket);
+
+/// 批量命令事务。
+///
+/// 先将管道写入与寄存器读取排入队列，再由 [`Transaction::flush`] 一次性发出。
+/// 固件每次只接受一个管道写入或寄存器选择，因此无法把多条命令合并进同一个
+/// 传输；`flush` 能做的是在不改变芯片状态的前提下省去多余的传输：
+///
+/// - 与上一次写入的轴号相同的轴切换命令（`0x?0f`）被省略；
+/// - 向数据寄存器（管道 6、7）重复写入相同的值被省略。
+///
+/// 这些判断只依据本事务中已经发出的写入，不假设事务开始前的芯片状态。
+#[derive(Debug)]
+pub struct Transaction<'a, T: UsbContext = GlobalContext> {
+    controller: &'a USB1020Controller<T>,
+    operations: Vec<Operation>,
+    reads: usize,
+}
+
+/// 事务执行后的寄存器读取结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Responses {
+    values: Vec<[u8; 2]>,
+    transfers: usize,
+}
+
+impl<'a, T: UsbContext> Transaction<'a, T> {
+    pub(crate) fn new(controller: &'a USB1020Controller<T>) -> Self {
+        Self {
+            controller,
+            operations: Vec::new(),
+            reads: 0,
+        }
+    }
+
+    /// 排入一次管道写入
+    pub fn write_pipe(&mut self, pipe: u8, data: u16) -> &mut Self {
+        self.operations.push(Operation::WritePipe { pipe, data });
+        self
+    }
+
+    /// 排入一次轴切换命令，之后的 RR1、RR2 读取与 WR1~WR3 写入针对该轴
+    pub fn select(&mut self, axis: Axis) -> &mut Self {
+        self.write_pipe(0, axis.as_data())
+    }
+
+    /// 排入一条命令，高字节为轴号，低字节为命令码，例如 `command(Axis::X, 0x26)` 即 `0x0126`
+    pub fn command(&mut self, axes: impl Into<Axes>, code: u8) -> &mut Self {
+        self.write_pipe(0, crate::drive::command_word(axes, code))
+    }
+
+    /// 排入一条数据写入命令：先写入 32 位数据，再发出命令
+    pub fn write_data(&mut self, axes: impl Into<Axes>, code: u8, data: u32) -> &mut Self {
+        self.set_u32_data(data).command(axes, code)
+    }
+
+    /// 排入 32 位数据写入（管道 7 为低 16 位，管道 6 为高 16 位）
+    pub fn set_u32_data(&mut self, data: u32) -> &mut Self {
+        let [data_0, data_1, data_2, data_3] = data.to_le_bytes();
+        self.write_pipe(7, u16::from_le_bytes([data_0, data_1]))
+            .write_pipe(6, u16::from_le_bytes([data_2, data_3]))
+    }
+
+    /// 排入一次读寄存器操作
+    pub fn read_rr(&mut self, rr_no: u8) -> Ticket {
+        self.operations.push(Operation::ReadRegister(rr_no));
+        self.reads += 1;
+        Ticket(self.reads - 1)
+    }
+
+    /// 排入一次 32 位数据读取，须先排入相应的读数据命令
+    pub fn read_u32_data(&mut self) -> U32Ticket {
+        U32Ticket(self.read_rr(7), self.read_rr(6))
+    }
+
+    /// 已排队的操作需要的 USB 传输次数，依次为逐条执行时与经 [`flush`](Self::flush)
+    /// 去除多余写入后的次数
+    pub fn transfer_counts(&self) -> (usize, usize) {
+        (
+            transfers(&self.operations),
+            transfers(&optimize(&self.operations)),
+        )
+    }
+
+    /// 依次执行队列中的操作，任一操作失败即停止
+    pub fn flush(self) -> Result<Responses, Error> {
+        self.execute().during("transaction", None)
+    }
+
+    fn execute(self) -> Result<Responses, Error> {
+        let operations = optimize(&self.operations);
+        let mut responses = Responses {
+            values: Vec::with_capacity(self.reads),
+            transfers: 0,
+        };
+
+        for operation in operations {
+            match operation {
+                Operation::WritePipe { pipe, data } => {
+                    self.controller.write_pipe(pipe, data)?;
+                    responses.transfers += 2;
+                }
+                Operation::ReadRegister(rr_no) => {
+                    responses.values.push(self.controller.pull_rr(rr_no)?);
+                    responses.transfers += 3;
+                }
+            }
+        }
+
+        Ok(responses)
+    }
+}
+
+impl Responses {
+    /// 读寄存器操作取回的原始值
+    pub fn get(&self, ticket: Ticket) -> [u8; 2] {
+        self.values[ticket.0]
+    }
+
+    /// 将读取结果解析为寄存器结构
+    pub fn decode<T>(&self, ticket: Ticket) -> Result<T, Error>
+    where
+        T: for<'b> TryFrom<&'b [u8], Error = DekuError>,
+    {
+        T::try_from(self.get(ticket).as_ref()).map_err(Error::DecodeError)
+    }
+
+    /// 32 位数据读取的结果
+    pub fn get_u32(&self, ticket: U32Ticket) -> u32 {
+        let [ep_0, ep_1] = self.get(ticket.0);
+        let [ep_2, ep_3] = self.get(ticket.1);
+        u32::from_le_bytes([ep_0, ep_1, ep_2, ep_3])
+    }
+
+    /// 本事务实际发出的 USB 传输次数（每个读寄存器操作计为一次写入和两次读取）
+    pub fn transfers(&self) -> usize {
+        self.transfers
+    }
+}
+
+/// 执行一组操作需要的 USB 传输次数：管道写入 2 次，读寄存器 3 次
+pub(crate) fn transfers(operations: &[Operation]) -> usize {
+    operations
+        .iter()
+        .map(|operation| match operation {
+            Operation::WritePipe { .. } => 2,
+            Operation::ReadRegister(_) => 3,
+        })
+        .sum()
+}
+
+/// 去除不改变芯片状态的多余写入后的操作序列
+pub(crate) fn optimize(operations: &[Operation]) -> Vec<Operation> {
+    let mut last_command: Option<u16> = None;
+    let mut last_data: [Option<u16>; 2] = [None, None];
+
+    operations
+        .iter()
+        .copied()
+        .filter(|operation| match *operation {
+            Operation::WritePipe {
+                pipe: 0,
+                data: 0x8000,
+            } => {
+                // 复位命令清空芯片内的所有寄存器
+                last_command = None;
+                last_data = [None, None];
+                true
+            }
+            Operation::WritePipe { pipe: 0, data } => {
+                let redundant = data & 0x00ff == 0x0f
+                    && matches!(last_command, Some(last) if last & 0xff00 == data & 0xff00);
+                last_command = Some(data);
+                !redundant
+            }
+            Operation::WritePipe {
+                pipe: pipe @ (6 | 7),
+                data,
+            } => {
+                let slot = &mut last_data[usize::from(pipe - 6)];
+                let redundant = *slot == Some(data);
+                *slot = Some(data);
+                !redundant
+            }
+            _ => true,
+        })
+        .collect()
+}
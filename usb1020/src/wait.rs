@@ -0,0 +1,157 @@
+use crate::common::{Axes, Axis, CounterType};
+use crate::register::AxisExternalSignal;
+use crate::{Error, USB1020Controller};
+use rusb::UsbContext;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 取消等待的令牌，克隆后可在其他线程中调用 [`cancel`](Self::cancel)
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// 等待函数的轮询选项
+#[derive(Debug, Clone)]
+pub struct WaitOptions {
+    /// 两次读取寄存器之间的间隔
+    pub poll_interval: Duration,
+    /// 被取消时等待函数返回 [`Error::Cancelled`]
+    pub cancel: Option<CancellationToken>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(10),
+            cancel: None,
+        }
+    }
+}
+
+impl WaitOptions {
+    /// 反复调用 `poll` 直到其返回 `Some`，超时或被取消时返回错误
+    pub(crate) fn poll<R>(
+        &self,
+        timeout: Duration,
+        mut poll: impl FnMut() -> Result<Option<R>, Error>,
+    ) -> Result<R, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(result) = poll()? {
+                return Ok(result);
+            }
+            if matches!(&self.cancel, Some(token) if token.is_cancelled()) {
+                return Err(Error::Cancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::WaitTimeout(timeout));
+            }
+            thread::sleep(self.poll_interval.min(deadline - now));
+        }
+    }
+}
+
+/// [`AxisExternalSignal`] 中的各个输入信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Signal {
+    In0,
+    In1,
+    In2,
+    In3,
+    /// 正方向点动输入 EXPP
+    JogPlus,
+    /// 反方向点动输入 EXPM
+    JogMinus,
+    /// 伺服电机到位信号 INPOS
+    InPosition,
+    /// 伺服马达报警信号 ALARM
+    ServoAlarm,
+}
+
+impl AxisExternalSignal {
+    /// 指定信号的电平
+    pub fn level(&self, signal: Signal) -> bool {
+        match signal {
+            Signal::In0 => self.in0,
+            Signal::In1 => self.in1,
+            Signal::In2 => self.in2,
+            Signal::In3 => self.in3,
+            Signal::JogPlus => self.p_p,
+            Signal::JogMinus => self.p_m,
+            Signal::InPosition => self.in_position,
+            Signal::ServoAlarm => self.servo_alarm,
+        }
+    }
+}
+
+impl<T: UsbContext> USB1020Controller<T> {
+    /// 等待指定的轴都结束驱动。
+    ///
+    /// 若某个轴因出错而停止，返回其 RR2 对应的错误，见 [`RR2::check`](crate::register::RR2::check)。
+    pub fn wait_idle(
+        &self,
+        axes: Axes,
+        timeout: Duration,
+        options: &WaitOptions,
+    ) -> Result<(), Error> {
+        let rr0 = options.poll(timeout, || {
+            let rr0 = self.get_rr0()?;
+            Ok(axes.iter().all(|axis| !rr0.drv(axis)).then_some(rr0))
+        })?;
+        for axis in axes.iter().filter(|axis| rr0.error(*axis)) {
+            self.get_rr2(axis)?.check(axis)?;
+        }
+        Ok(())
+    }
+
+    /// 等待指定轴的输入信号变为 `level` 电平
+    pub fn wait_for_signal(
+        &self,
+        axis: Axis,
+        signal: Signal,
+        level: bool,
+        timeout: Duration,
+        options: &WaitOptions,
+    ) -> Result<(), Error> {
+        options.poll(timeout, || {
+            let signals = self.get_signals(axis)?;
+            Ok((signals.level(signal) == level).then_some(()))
+        })
+    }
+
+    /// 等待指定轴的逻辑/实位计数器满足 `predicate`，返回满足时读到的位置
+    pub fn wait_position(
+        &self,
+        axis: Axis,
+        counter: CounterType,
+        mut predicate: impl FnMut(i32) -> bool,
+        timeout: Duration,
+        options: &WaitOptions,
+    ) -> Result<i32, Error> {
+        options.poll(timeout, || {
+            let position = match counter {
+                CounterType::Logic => self.read_lp(axis)?,
+                CounterType::Fact => self.read_ep(axis)?,
+            } as i32;
+            Ok(predicate(position).then_some(position))
+        })
+    }
+}